@@ -0,0 +1,124 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use lisp_rs::{parse_program, parse_sexpr, DEFAULT_MAX_DEPTH};
+
+/// Generates a single list of roughly `len` bytes, made up of a mix of
+/// nested lists, symbols, numbers and strings.
+fn generate_sexpr(len: usize) -> String {
+    let form = "(define (area-of shape) (* 3.14159 (radius shape) \"units\" 'quoted 42)) ";
+    let mut sexpr = String::with_capacity(len + form.len());
+
+    sexpr.push('(');
+
+    while sexpr.len() < len {
+        sexpr.push_str(form);
+    }
+
+    sexpr.push(')');
+    sexpr
+}
+
+/// Generates a program of roughly `len` bytes that uses the same handful of
+/// symbols over and over, as real code does.
+fn generate_repeated_symbols(len: usize) -> String {
+    let form = "(define (f x y) (if (< x y) (+ x (* y y)) (lambda (z) (- x z)))) ";
+
+    form.repeat(len / form.len() + 1)
+}
+
+/// Generates lists nested `depth` deep around a single symbol, with a few
+/// atoms alongside each level so that it isn't all parens.
+fn generate_nested(depth: usize) -> String {
+    let mut sexpr = String::with_capacity(depth * 8);
+
+    for _ in 0..depth {
+        sexpr.push_str("(f 1 ");
+    }
+
+    sexpr.push('x');
+
+    for _ in 0..depth {
+        sexpr.push(')');
+    }
+
+    sexpr
+}
+
+/// Generates a single list of `count` small lists side by side.
+fn generate_siblings(count: usize) -> String {
+    format!("({})", "(a (b) c) ".repeat(count))
+}
+
+fn parse_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for (name, len) in [("small", 100), ("medium", 16 << 10), ("large", 1 << 20)] {
+        let sexpr = generate_sexpr(len);
+
+        group.throughput(Throughput::Bytes(sexpr.len() as u64));
+        group.sample_size(if len > 1 << 16 { 20 } else { 100 });
+        group.bench_function(name, |b| b.iter(|| parse_sexpr(black_box(&sexpr)).unwrap()));
+    }
+
+    group.finish();
+}
+
+fn parse_nested(c: &mut Criterion) {
+    let sexpr = generate_nested(DEFAULT_MAX_DEPTH);
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(sexpr.len() as u64));
+    group.bench_function("nested", |b| {
+        b.iter(|| parse_sexpr(black_box(&sexpr)).unwrap())
+    });
+    group.finish();
+}
+
+/// Parsing time should grow linearly with the number of sibling lists, so
+/// each step up here should take about ten times as long as the last.
+fn parse_siblings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse/siblings");
+
+    for count in [100, 1_000, 10_000] {
+        let sexpr = generate_siblings(count);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_function(count.to_string(), |b| {
+            b.iter(|| parse_sexpr(black_box(&sexpr)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn parse_repeated_symbols(c: &mut Criterion) {
+    let program = generate_repeated_symbols(1 << 20);
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(program.len() as u64));
+    group.sample_size(20);
+    group.bench_function("repeated_symbols", |b| {
+        b.iter(|| parse_program(black_box(&program)).unwrap())
+    });
+    group.finish();
+}
+
+fn clone_large(c: &mut Criterion) {
+    let node = parse_sexpr(&generate_sexpr(1 << 20)).unwrap();
+    let shared = node.to_shared();
+
+    let mut group = c.benchmark_group("clone");
+    group.sample_size(20);
+    group.bench_function("owned", |b| b.iter(|| black_box(&node).clone()));
+    group.bench_function("shared", |b| b.iter(|| black_box(&shared).clone()));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    parse_sizes,
+    parse_nested,
+    parse_siblings,
+    parse_repeated_symbols,
+    clone_large
+);
+criterion_main!(benches);
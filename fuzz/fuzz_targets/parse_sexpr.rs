@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Whatever the input, parsing it should give back a node or an error rather
+// than panicking, and so should displaying either one.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(code) = std::str::from_utf8(data) {
+        match lisp_rs::parse_sexpr(code) {
+            Ok(node) => {
+                let _ = node.to_string();
+            }
+            Err(e) => {
+                let _ = e.to_string();
+                let _ = e.position();
+            }
+        }
+    }
+});
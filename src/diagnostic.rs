@@ -0,0 +1,103 @@
+use miette::{Diagnostic, LabeledSpan};
+
+use crate::parser::SexprSyntaxError;
+
+/// Points at where in the source an error was found, for rendering with
+/// `miette`. The error doesn't hold on to the source itself, so it needs to
+/// be attached with `Report::with_source_code` to show the snippet.
+impl Diagnostic for SexprSyntaxError {
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let position = self.position()?;
+        let label = match self {
+            Self::UnexpectedEof(_) => "never finished",
+            Self::UnexpectedCloseParen(_) => "nothing to close here",
+            Self::MismatchedBracket { open, close } => {
+                return Some(Box::new(
+                    vec![
+                        LabeledSpan::at_offset(open.offset, "opened here"),
+                        LabeledSpan::at_offset(close.offset, "but closed here"),
+                    ]
+                    .into_iter(),
+                ))
+            }
+            Self::TrailingTokens(_) => "expected the end of the input",
+            Self::UnterminatedString(_) => "string starts here",
+            Self::UnterminatedSymbol(_) => "symbol starts here",
+            Self::UnterminatedComment(_) => "comment starts here",
+            Self::DanglingReaderMacro(_) => "nothing to apply this to",
+            Self::DisabledReaderMacro(_) => "reader macros are turned off",
+            Self::InvalidDottedPair(_) => "misplaced dot",
+            Self::InvalidEscape(..) => "unknown escape sequence",
+            Self::InvalidNumber(..) => "not a valid number",
+            Self::IntegerOverflow(..) => "too large for a 64-bit integer",
+            Self::InvalidChar(..) => "not a valid character",
+            Self::InvalidSymbol(..) => "not allowed in a symbol",
+            Self::DepthLimitExceeded(_) => "nested too deeply",
+            Self::Empty => return None,
+        };
+
+        Some(Box::new(std::iter::once(LabeledSpan::at_offset(
+            position.offset,
+            label,
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::{GraphicalReportHandler, GraphicalTheme, Report};
+
+    use crate::parser::parse_sexpr;
+
+    fn render(code: &str) -> String {
+        let report = Report::new(parse_sexpr(code).unwrap_err()).with_source_code(code.to_owned());
+        let mut out = String::new();
+
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+
+        out
+    }
+
+    #[test]
+    fn unclosed_list() {
+        assert_eq!(
+            render("(+ 1 2"),
+            concat!(
+                "  × Input ended before the form at line 1, column 1 was finished\n",
+                "   ╭────\n",
+                " 1 │ (+ 1 2\n",
+                "   · ▲\n",
+                "   · ╰── never finished\n",
+                "   ╰────\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn points_at_the_offending_paren() {
+        let rendered = render("(a\n  b))");
+
+        assert!(rendered.contains(" 2 │   b))\n   ·     ▲\n   ·     ╰── nothing to close here\n"));
+    }
+
+    #[test]
+    fn mismatched_bracket_labels_both_ends() {
+        let rendered = render("(a [b c)");
+
+        assert!(rendered.contains(concat!(
+            " 1 │ (a [b c)\n",
+            "   ·    ▲   ▲\n",
+            "   ·    │   ╰── but closed here\n",
+            "   ·    ╰── opened here\n",
+        )));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let rendered = render("(\"é\" #\\bogus)");
+
+        assert!(rendered.contains(" 1 │ (\"é\" #\\bogus)\n   ·      ▲\n"));
+    }
+}
@@ -0,0 +1,1676 @@
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, error::Error, fmt, rc::Rc};
+
+use crate::{
+    parser::{cmp_int_float, gcd, Atom, Float, Node},
+    symbol::Symbol,
+};
+
+/// Evaluates an expression. Symbols evaluate to whatever they're bound to in
+/// `env`, other atoms evaluate to themselves, and a list is a call, unless
+/// it's one of the special forms below. The head of a call is evaluated to
+/// find a closure to apply to the values of the rest of its elements, except
+/// that the name of a builtin at the head refers to that builtin unless it's
+/// been bound to something else. The builtins are:
+///
+/// - The arithmetic operators `+`, `-`, `*` and `/`.
+/// - The comparisons `=`, `<`, `>`, `<=` and `>=`, which take one or more
+///   numbers and check that each is related that way to the next.
+/// - `not`, which gives `#t` for `#f` and `#f` for anything else.
+/// - The list primitives: `(list values...)` makes a list of its arguments,
+///   `(cons value list)` makes a new list with `value` in front of `list`,
+///   and `car` and `cdr` give the first element of a non-empty list and a
+///   list of the rest of its elements respectively.
+///
+/// The special forms are:
+///
+/// - `(define name value)` binds `name` to the value of `value` in `env`,
+///   replacing any existing binding there, and evaluates to `name`.
+/// - `(if test then else)` evaluates `then` if `test` is truthy and `else`
+///   otherwise, without evaluating the other. Without an `else`, a falsy
+///   test makes the whole form evaluate to `Value::Nil`.
+/// - `(lambda (params...) body...)` evaluates to a closure over `env`.
+///   Calling it binds its parameters to the arguments in a new scope inside
+///   `env` and evaluates the body there, giving the value of its last
+///   expression.
+/// - `(let ((name value)...) body...)` evaluates each `value` in `env`, then
+///   binds them all in a new scope inside `env` to evaluate the body in,
+///   giving the value of its last expression. `let*` is the same, except
+///   that each binding is made before the next value is evaluated, so later
+///   values can refer to earlier names.
+/// - `(and tests...)` and `(or tests...)` evaluate their tests in turn,
+///   stopping at the first falsy one for `and` and the first truthy one for
+///   `or`, and give `#t` or `#f` depending on whether they stopped. With no
+///   tests, `and` gives `#t` and `or` `#f`.
+///
+/// How arithmetic mixes integers, rationals and floats is up to the
+/// `NumericPolicy` of `env`. Under the default, arithmetic on integers stays
+/// in integers, with division truncating towards zero, while any float among
+/// the operands makes the result a float. Comparisons between an integer and
+/// a float compare their exact values, even where converting the integer to
+/// a float would round it.
+///
+/// Calls in tail position, such as the last expression of a body or a branch
+/// of an `if`, don't use up any stack, so recursion through them can go as
+/// deep as it likes. Anything else, such as an argument to a call, has to be
+/// evaluated inside the expression around it. Nesting more of those inside
+/// one another than the `depth_limit` of `env`, whether in the code itself
+/// or through calls, is an `EvalError::DepthLimitExceeded`.
+pub fn eval(node: &Node, env: &mut Env) -> Result<Value, EvalError> {
+    eval_nested(node, env, env.depth_limit())
+}
+
+/// How many evaluations that aren't in tail position an `Env` lets nest
+/// inside one another unless told otherwise.
+///
+/// Anything that follows a tree down to its leaves by recursing, as `eval`
+/// does for these, needs a limit on how deep it goes, or a deep enough tree
+/// overflows the stack and aborts the whole program. Rust gives spawned
+/// threads 2 MiB stacks by default, and debug builds use far more of the
+/// stack for each level than release builds, so this limit and the others
+/// that refer to it are set low enough for debug builds to stay within
+/// that with room to spare. Code running on a bigger stack, or only in
+/// release builds, can raise them.
+pub const DEFAULT_EVAL_DEPTH_LIMIT: usize = 200;
+
+/// Evaluates `node`, inside which up to `depth_left` evaluations, including
+/// its own, may nest.
+fn eval_nested(node: &Node, env: &mut Env, depth_left: usize) -> Result<Value, EvalError> {
+    let depth_left = depth_left
+        .checked_sub(1)
+        .ok_or(EvalError::DepthLimitExceeded)?;
+
+    // Calls in tail position come back here rather than being made from
+    // inside `step`, so a chain of them runs in constant stack space.
+    let mut step = eval_step(node, env, depth_left)?;
+
+    loop {
+        match step {
+            Step::Done(value) => return Ok(value),
+            Step::Call(body, mut scope) => {
+                let last = eval_body(&body, &mut scope, depth_left)?;
+
+                step = eval_step(last, &mut scope, depth_left)?;
+            }
+        }
+    }
+}
+
+/// What's left to do after evaluating everything in an expression except a
+/// call in tail position.
+enum Step {
+    Done(Value),
+    /// Evaluate a closure's body in the scope its arguments were bound in.
+    Call(Rc<[Node]>, Env),
+}
+
+/// Evaluates `node`, following its tail positions through special forms,
+/// until what's left is either a value or a call to a closure. Anything else
+/// it evaluates may have up to `depth_left` evaluations nested inside it.
+fn eval_step(mut node: &Node, env: &mut Env, depth_left: usize) -> Result<Step, EvalError> {
+    let mut env = env.clone();
+
+    loop {
+        let (head, rest) = match node {
+            Node::Atom(Atom::Symbol(name)) => {
+                return env
+                    .get(*name)
+                    .map(Step::Done)
+                    .ok_or_else(|| EvalError::UnboundSymbol(name.to_string()))
+            }
+            Node::Atom(Atom::Nil) => return Ok(Step::Done(Value::Nil)),
+            Node::Atom(Atom::RadixInt(i, _)) => return Ok(Step::Done(Value::Atom(Atom::Int(*i)))),
+            Node::Atom(atom) => return Ok(Step::Done(Value::Atom(atom.clone()))),
+            Node::List(elements) => match elements.split_first() {
+                Some(split) => split,
+                None => return Err(EvalError::InvalidCall(node.to_string())),
+            },
+            Node::Pair(..) | Node::Vector(_) => {
+                return Err(EvalError::InvalidCall(node.to_string()))
+            }
+        };
+
+        if let Node::Atom(Atom::Symbol(operator)) = head {
+            let done = match operator.as_str() {
+                "define" => Some(eval_define(node, rest, &mut env, depth_left)),
+                "lambda" => Some(eval_lambda(node, rest, &env)),
+                "and" => Some(eval_and_or(rest, &mut env, false, depth_left)),
+                "or" => Some(eval_and_or(rest, &mut env, true, depth_left)),
+                _ => None,
+            };
+
+            if let Some(value) = done {
+                return value.map(Step::Done);
+            }
+
+            match operator.as_str() {
+                "if" => match eval_if(node, rest, &mut env, depth_left)? {
+                    Some(branch) => {
+                        node = branch;
+                        continue;
+                    }
+                    None => return Ok(Step::Done(Value::Nil)),
+                },
+                "let" | "let*" => {
+                    let (mut scope, body) =
+                        eval_let(node, rest, &mut env, operator == "let*", depth_left)?;
+
+                    node = eval_body(body, &mut scope, depth_left)?;
+                    env = scope;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if env.get(*operator).is_none() {
+                let args = eval_args(rest, &mut env, depth_left)?;
+
+                return builtin(operator.as_str(), args, env.policy()).map(Step::Done);
+            }
+        }
+
+        let function = eval_nested(head, &mut env, depth_left)?;
+        let args = eval_args(rest, &mut env, depth_left)?;
+
+        return apply(head, function, args).map(|(body, scope)| Step::Call(body, scope));
+    }
+}
+
+fn eval_define(
+    form: &Node,
+    args: &[Node],
+    env: &mut Env,
+    depth_left: usize,
+) -> Result<Value, EvalError> {
+    let (name, value) = match args {
+        [Node::Atom(Atom::Symbol(name)), value] => (name, value),
+        _ => return Err(EvalError::MalformedForm(form.to_string())),
+    };
+
+    let value = eval_nested(value, env, depth_left)?;
+    env.set(*name, value);
+
+    Ok(Value::Atom(Atom::Symbol(*name)))
+}
+
+/// Evaluates the test of an `if` form, giving the branch to take, if any.
+fn eval_if<'a>(
+    form: &Node,
+    args: &'a [Node],
+    env: &mut Env,
+    depth_left: usize,
+) -> Result<Option<&'a Node>, EvalError> {
+    let (test, then, otherwise) = match args {
+        [test, then] => (test, then, None),
+        [test, then, otherwise] => (test, then, Some(otherwise)),
+        _ => return Err(EvalError::MalformedForm(form.to_string())),
+    };
+
+    if eval_nested(test, env, depth_left)?.is_truthy() {
+        Ok(Some(then))
+    } else {
+        Ok(otherwise)
+    }
+}
+
+fn eval_lambda(form: &Node, args: &[Node], env: &Env) -> Result<Value, EvalError> {
+    let (params, body) = match args {
+        [Node::List(params), body @ ..] if !body.is_empty() => (params, body),
+        _ => return Err(EvalError::MalformedForm(form.to_string())),
+    };
+
+    let params = params
+        .iter()
+        .map(|param| match param {
+            Node::Atom(Atom::Symbol(name)) => Ok(*name),
+            _ => Err(EvalError::MalformedForm(form.to_string())),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Value::Closure {
+        params,
+        body: body.into(),
+        env: env.clone(),
+    })
+}
+
+/// Evaluates the bindings of a `let` form, or a `let*` form if `sequential`
+/// is set, giving the scope they're bound in and the body to evaluate there.
+fn eval_let<'a>(
+    form: &Node,
+    args: &'a [Node],
+    env: &mut Env,
+    sequential: bool,
+    depth_left: usize,
+) -> Result<(Env, &'a [Node]), EvalError> {
+    let (bindings, body) = match args {
+        [Node::List(bindings), body @ ..] if !body.is_empty() => (bindings, body),
+        _ => return Err(EvalError::MalformedForm(form.to_string())),
+    };
+
+    let mut scope = env.new_child();
+
+    for binding in bindings {
+        let (name, value) = match binding.as_list() {
+            Some([Node::Atom(Atom::Symbol(name)), value]) => (name, value),
+            _ => return Err(EvalError::MalformedForm(form.to_string())),
+        };
+
+        let value = if sequential {
+            eval_nested(value, &mut scope, depth_left)?
+        } else {
+            eval_nested(value, env, depth_left)?
+        };
+
+        scope.set(*name, value);
+    }
+
+    Ok((scope, body))
+}
+
+/// Evaluates an `or` form if `stop_on` is true, or an `and` form if not.
+fn eval_and_or(
+    tests: &[Node],
+    env: &mut Env,
+    stop_on: bool,
+    depth_left: usize,
+) -> Result<Value, EvalError> {
+    for test in tests {
+        if eval_nested(test, env, depth_left)?.is_truthy() == stop_on {
+            return Ok(Value::Atom(Atom::Bool(stop_on)));
+        }
+    }
+
+    Ok(Value::Atom(Atom::Bool(!stop_on)))
+}
+
+/// Evaluates every expression of a non-empty body but the last, which is in
+/// tail position and left for the caller to evaluate.
+fn eval_body<'a>(
+    body: &'a [Node],
+    env: &mut Env,
+    depth_left: usize,
+) -> Result<&'a Node, EvalError> {
+    let (last, init) = body.split_last().expect("bodies are never empty");
+
+    for expr in init {
+        eval_nested(expr, env, depth_left)?;
+    }
+
+    Ok(last)
+}
+
+fn eval_args(args: &[Node], env: &mut Env, depth_left: usize) -> Result<Vec<Value>, EvalError> {
+    args.iter()
+        .map(|arg| eval_nested(arg, env, depth_left))
+        .collect()
+}
+
+/// Prepares to call `function`, which was what `head` evaluated to, with
+/// `args`, giving its body and the scope its parameters are bound in.
+fn apply(head: &Node, function: Value, args: Vec<Value>) -> Result<(Rc<[Node]>, Env), EvalError> {
+    let (params, body, env) = match function {
+        Value::Closure { params, body, env } => (params, body, env),
+        _ => return Err(EvalError::UnknownOperator(head.to_string())),
+    };
+
+    if params.len() != args.len() {
+        return Err(EvalError::WrongArity(head.to_string()));
+    }
+
+    let mut scope = env.new_child();
+
+    for (param, arg) in params.iter().zip(args) {
+        scope.set(*param, arg);
+    }
+
+    Ok((body, scope))
+}
+
+/// The result of evaluating an expression.
+///
+/// Values are compared structurally, apart from the scopes of closures. Two
+/// closures are equal if their parameters and bodies are equal and they were
+/// made in the very same scope, as `Env`'s `==` compares scopes by identity.
+/// So evaluating the same `lambda` twice in one scope gives equal closures,
+/// but evaluating it in two different scopes doesn't, even if they hold the
+/// same bindings.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Atom(Atom),
+    /// The value of forms that have nothing more useful to evaluate to,
+    /// such as an `if` without an `else` whose test fails.
+    Nil,
+    List(Vec<Value>),
+    /// A function made by `lambda`, along with the scope it was made in.
+    Closure {
+        params: Rc<[Symbol]>,
+        body: Rc<[Node]>,
+        env: Env,
+    },
+}
+
+impl Value {
+    /// Whether the value counts as true in a test. As in Scheme, anything
+    /// other than `#f` does.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Atom(Atom::Bool(false)))
+    }
+}
+
+impl From<Atom> for Value {
+    fn from(atom: Atom) -> Self {
+        Self::Atom(atom)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Atom(atom) => write!(f, "{}", atom),
+            Self::Nil => write!(f, "()"),
+            Self::List(v) => write!(
+                f,
+                "({})",
+                v.iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Self::Closure { .. } => write!(f, "#<procedure>"),
+        }
+    }
+}
+
+/// A scope that symbols can be bound to values in. Cloning an `Env` doesn't
+/// copy its bindings, so every clone sees the others' changes, and child
+/// scopes refer back to their parent in the same way. Two `Env`s are equal
+/// only if they're clones of each other.
+#[derive(Clone)]
+pub struct Env {
+    scope: Rc<Scope>,
+    depth_limit: usize,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self {
+            scope: Rc::default(),
+            depth_limit: DEFAULT_EVAL_DEPTH_LIMIT,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    bindings: RefCell<HashMap<Symbol, Value>>,
+    parent: Option<Env>,
+    policy: NumericPolicy,
+}
+
+impl Env {
+    /// Creates an empty top-level scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty top-level scope whose arithmetic follows `policy`.
+    pub fn with_policy(policy: NumericPolicy) -> Self {
+        Self {
+            scope: Rc::new(Scope {
+                policy,
+                ..Scope::default()
+            }),
+            depth_limit: DEFAULT_EVAL_DEPTH_LIMIT,
+        }
+    }
+
+    /// The rules for arithmetic in this scope, which nested scopes inherit.
+    pub fn policy(&self) -> NumericPolicy {
+        self.scope.policy
+    }
+
+    /// How many evaluations that aren't in tail position `eval` lets nest
+    /// inside one another when it's given this `Env`. This starts out as
+    /// `DEFAULT_EVAL_DEPTH_LIMIT`, and nested scopes inherit it.
+    pub fn depth_limit(&self) -> usize {
+        self.depth_limit
+    }
+
+    /// Sets the `depth_limit` of this `Env` and of scopes nested inside it
+    /// from now on, but not of its clones.
+    pub fn set_depth_limit(&mut self, limit: usize) {
+        self.depth_limit = limit;
+    }
+
+    /// Creates a scope nested inside this one. Bindings made in it shadow
+    /// this scope's without changing them.
+    pub fn new_child(&self) -> Self {
+        Self {
+            scope: Rc::new(Scope {
+                bindings: RefCell::default(),
+                parent: Some(self.clone()),
+                policy: self.scope.policy,
+            }),
+            depth_limit: self.depth_limit,
+        }
+    }
+
+    /// Looks `name` up in this scope, then in each enclosing one in turn.
+    pub fn get(&self, name: impl Into<Symbol>) -> Option<Value> {
+        let name = name.into();
+        let mut env = self;
+
+        loop {
+            if let Some(value) = env.scope.bindings.borrow().get(&name) {
+                return Some(value.clone());
+            }
+
+            env = env.scope.parent.as_ref()?;
+        }
+    }
+
+    /// Binds `name` in this scope, replacing any existing binding of it here.
+    pub fn set(&mut self, name: impl Into<Symbol>, value: impl Into<Value>) {
+        self.scope
+            .bindings
+            .borrow_mut()
+            .insert(name.into(), value.into());
+    }
+}
+
+impl PartialEq for Env {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+// A closure bound in the scope it captures refers back to itself, so this
+// can't print the bindings without going round in circles.
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Env").finish_non_exhaustive()
+    }
+}
+
+fn builtin(
+    operator: &str,
+    mut args: Vec<Value>,
+    policy: NumericPolicy,
+) -> Result<Value, EvalError> {
+    match operator {
+        "+" | "-" | "*" | "/" => arithmetic(operator, args, policy).map(Value::Atom),
+        "=" | "<" | ">" | "<=" | ">=" => compare(operator, args).map(Value::Atom),
+        "list" => Ok(Value::List(args)),
+        _ => match (operator, args.len()) {
+            ("not", 1) => Ok(Value::Atom(Atom::Bool(!args[0].is_truthy()))),
+            ("car", 1) | ("cdr", 1) => {
+                let mut list = into_list(args.remove(0))?.into_iter();
+
+                match (operator, list.next()) {
+                    (_, None) => Err(EvalError::EmptyList(operator.to_owned())),
+                    ("car", Some(first)) => Ok(first),
+                    _ => Ok(Value::List(list.collect())),
+                }
+            }
+            ("cons", 2) => {
+                let mut list = into_list(args.remove(1))?;
+                list.insert(0, args.remove(0));
+
+                Ok(Value::List(list))
+            }
+            ("not", _) | ("car", _) | ("cdr", _) | ("cons", _) => {
+                Err(EvalError::WrongArity(operator.to_owned()))
+            }
+            _ => Err(EvalError::UnknownOperator(operator.to_owned())),
+        },
+    }
+}
+
+fn into_list(value: Value) -> Result<Vec<Value>, EvalError> {
+    match value {
+        Value::List(list) => Ok(list),
+        other => Err(EvalError::NotAList(other.to_string())),
+    }
+}
+
+fn arithmetic(operator: &str, args: Vec<Value>, policy: NumericPolicy) -> Result<Atom, EvalError> {
+    let (identity, op): (_, fn(_, _, _) -> _) = match operator {
+        "+" => (Atom::Int(0), add),
+        "-" => (Atom::Int(0), sub),
+        "*" => (Atom::Int(1), mul),
+        "/" => (Atom::Int(1), div),
+        _ => unreachable!("not an arithmetic operator"),
+    };
+
+    let mut args = numbers(args)?.into_iter();
+
+    // Like other Lisps, `(- x)` negates and `(/ x)` takes the reciprocal, but
+    // unlike `+` and `*` they need at least that one argument.
+    let first = match (operator, args.len()) {
+        ("-", 0) | ("/", 0) => return Err(EvalError::WrongArity(operator.to_owned())),
+        ("-", 1) | ("/", 1) | ("+", _) | ("*", _) => identity,
+        _ => args.next().unwrap(),
+    };
+
+    args.try_fold(first, |a, b| op(a, b, policy))
+}
+
+fn compare(operator: &str, args: Vec<Value>) -> Result<Atom, EvalError> {
+    let holds: fn(Ordering) -> bool = match operator {
+        "=" => Ordering::is_eq,
+        "<" => Ordering::is_lt,
+        ">" => Ordering::is_gt,
+        "<=" => Ordering::is_le,
+        ">=" => Ordering::is_ge,
+        _ => unreachable!("not a comparison"),
+    };
+
+    let args = numbers(args)?;
+
+    if args.is_empty() {
+        return Err(EvalError::WrongArity(operator.to_owned()));
+    }
+
+    if operator == "=" {
+        return Ok(Atom::Bool(
+            args.windows(2).all(|pair| pair[0].numeric_eq(&pair[1])),
+        ));
+    }
+
+    // Integers and rationals are ordered exactly, as are integers against
+    // floats, to agree with `=`. `NaN` isn't ordered relative to anything, so
+    // every comparison with it is false.
+    Ok(Atom::Bool(args.windows(2).all(|pair| {
+        let ordering = match (&pair[0], &pair[1]) {
+            (Atom::Int(i), Atom::Float(f)) => cmp_int_float(*i, *f),
+            (Atom::Float(f), Atom::Int(i)) => cmp_int_float(*i, *f).map(Ordering::reverse),
+            (a, b) => match (as_exact(a), as_exact(b)) {
+                // Denominators are positive, so cross-multiplying keeps the
+                // order, and as `i128`s the products can't overflow.
+                (Some((a, b)), Some((c, d))) => {
+                    Some((a as i128 * d as i128).cmp(&(c as i128 * b as i128)))
+                }
+                _ => as_float(a).partial_cmp(&as_float(b)),
+            },
+        };
+
+        ordering.is_some_and(holds)
+    })))
+}
+
+/// Checks that every value is a number, unwrapping them into atoms.
+fn numbers(args: Vec<Value>) -> Result<Vec<Atom>, EvalError> {
+    args.into_iter()
+        .map(|arg| match arg {
+            Value::Atom(atom) if is_number(&atom) => Ok(atom),
+            other => Err(EvalError::NotANumber(other.to_string())),
+        })
+        .collect()
+}
+
+fn is_number(atom: &Atom) -> bool {
+    matches!(atom, Atom::Int(_) | Atom::Rational { .. } | Atom::Float(_))
+}
+
+fn as_float(atom: &Atom) -> Float {
+    match atom {
+        Atom::Int(i) => *i as Float,
+        Atom::Rational { num, den } => (*num as f64 / *den as f64) as Float,
+        Atom::Float(f) => *f,
+        _ => unreachable!("operands are checked to be numbers"),
+    }
+}
+
+/// Gives an integer or rational as a numerator and denominator.
+fn as_exact(atom: &Atom) -> Option<(i64, i64)> {
+    match atom {
+        Atom::Int(i) => Some((*i, 1)),
+        Atom::Rational { num, den } => Some((*num, *den)),
+        _ => None,
+    }
+}
+
+/// Makes the rational `num/den` in lowest terms, or an integer if it divides
+/// out evenly.
+fn rational(num: i64, den: i64) -> Result<Atom, EvalError> {
+    let (num, den) = if den < 0 {
+        (num.checked_neg(), den.checked_neg())
+    } else {
+        (Some(num), Some(den))
+    };
+    let (num, den) = num.zip(den).ok_or(EvalError::Overflow)?;
+    let divisor = gcd(num.unsigned_abs(), den.unsigned_abs()) as i64;
+
+    Ok(match (num / divisor, den / divisor) {
+        (num, 1) => Atom::Int(num),
+        (num, den) => Atom::Rational { num, den },
+    })
+}
+
+/// How arithmetic treats operands of different kinds of number. Integers
+/// and rationals are exact, while floats are not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumericPolicy {
+    /// Arithmetic on integers stays in integers, with division truncating
+    /// towards zero, while anything else among the operands makes the
+    /// result a float.
+    #[default]
+    PromoteOnMix,
+    /// Every result is a float, even one from only integers.
+    AlwaysFloat,
+    /// Arithmetic on integers and rationals stays exact, so dividing two
+    /// integers gives a rational if it doesn't divide out evenly. Any float
+    /// among the operands makes the result a float.
+    ExactRational,
+}
+
+type Exact = (i64, i64);
+
+/// Applies an operator to two numbers following `policy`, using `int_op` on
+/// integers, `exact_op` on numerators and denominators, and `float_op` on
+/// anything promoted to a float.
+fn numeric(
+    a: Atom,
+    b: Atom,
+    policy: NumericPolicy,
+    int_op: fn(i64, i64) -> Option<i64>,
+    exact_op: fn(Exact, Exact) -> Option<Exact>,
+    float_op: fn(Float, Float) -> Float,
+) -> Result<Atom, EvalError> {
+    match (policy, &a, &b) {
+        (NumericPolicy::PromoteOnMix, Atom::Int(a), Atom::Int(b)) => {
+            int_op(*a, *b).map(Atom::Int).ok_or(EvalError::Overflow)
+        }
+        (NumericPolicy::ExactRational, _, _) => match as_exact(&a).zip(as_exact(&b)) {
+            Some((a, b)) => {
+                let (num, den) = exact_op(a, b).ok_or(EvalError::Overflow)?;
+
+                rational(num, den)
+            }
+            None => Ok(Atom::Float(float_op(as_float(&a), as_float(&b)))),
+        },
+        _ => Ok(Atom::Float(float_op(as_float(&a), as_float(&b)))),
+    }
+}
+
+fn add(a: Atom, b: Atom, policy: NumericPolicy) -> Result<Atom, EvalError> {
+    numeric(
+        a,
+        b,
+        policy,
+        i64::checked_add,
+        |(a, b), (c, d)| {
+            Some((
+                a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?,
+                b.checked_mul(d)?,
+            ))
+        },
+        |a, b| a + b,
+    )
+}
+
+fn sub(a: Atom, b: Atom, policy: NumericPolicy) -> Result<Atom, EvalError> {
+    numeric(
+        a,
+        b,
+        policy,
+        i64::checked_sub,
+        |(a, b), (c, d)| {
+            Some((
+                a.checked_mul(d)?.checked_sub(c.checked_mul(b)?)?,
+                b.checked_mul(d)?,
+            ))
+        },
+        |a, b| a - b,
+    )
+}
+
+fn mul(a: Atom, b: Atom, policy: NumericPolicy) -> Result<Atom, EvalError> {
+    numeric(
+        a,
+        b,
+        policy,
+        i64::checked_mul,
+        |(a, b), (c, d)| Some((a.checked_mul(c)?, b.checked_mul(d)?)),
+        |a, b| a * b,
+    )
+}
+
+fn div(a: Atom, b: Atom, policy: NumericPolicy) -> Result<Atom, EvalError> {
+    if as_float(&b) == 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+
+    numeric(
+        a,
+        b,
+        policy,
+        i64::checked_div,
+        |(a, b), (c, d)| Some((a.checked_mul(d)?, b.checked_mul(c)?)),
+        |a, b| a / b,
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvalError {
+    UnboundSymbol(String),
+    /// The head of a list isn't an operator that can be called.
+    UnknownOperator(String),
+    /// Something other than a proper, non-empty list was called.
+    InvalidCall(String),
+    /// A special form such as `define` was used with the wrong shape.
+    MalformedForm(String),
+    NotANumber(String),
+    NotAList(String),
+    /// `car` or `cdr` was applied to the empty list.
+    EmptyList(String),
+    WrongArity(String),
+    DivisionByZero,
+    Overflow,
+    /// More evaluations nested inside one another than the `depth_limit` of
+    /// the `Env` they started in allows.
+    DepthLimitExceeded,
+}
+
+impl Error for EvalError {}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnboundSymbol(name) => write!(f, "Unbound symbol {}", name),
+            Self::UnknownOperator(op) => write!(f, "Unknown operator {}", op),
+            Self::InvalidCall(call) => write!(f, "Cannot evaluate {} as a call", call),
+            Self::MalformedForm(form) => write!(f, "Malformed special form {}", form),
+            Self::NotANumber(value) => write!(f, "Expected a number but found {}", value),
+            Self::NotAList(value) => write!(f, "Expected a list but found {}", value),
+            Self::EmptyList(op) => write!(f, "Cannot take the {} of an empty list", op),
+            Self::WrongArity(op) => write!(f, "Wrong number of arguments to {}", op),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::Overflow => write!(f, "Integer overflow"),
+            Self::DepthLimitExceeded => write!(f, "Evaluation nested too deeply"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval, Env, EvalError, NumericPolicy, Value, DEFAULT_EVAL_DEPTH_LIMIT};
+    use crate::parser::{parse_sexpr, parse_sexpr_with, Atom::*, ParseOptions};
+
+    fn eval_in(code: &str, env: &mut Env) -> Result<Value, EvalError> {
+        eval(&parse_sexpr(code).unwrap(), env)
+    }
+
+    fn eval_str(code: &str) -> Result<Value, EvalError> {
+        eval_in(code, &mut Env::new())
+    }
+
+    #[test]
+    fn atoms_evaluate_to_themselves() {
+        assert_eq!(eval_str("5").unwrap(), Value::Atom(Int(5)));
+        assert_eq!(
+            eval_str("\"hi\"").unwrap(),
+            Value::Atom(Str("hi".to_owned()))
+        );
+        assert_eq!(
+            eval_str(":key").unwrap(),
+            Value::Atom(Keyword("key".to_owned()))
+        );
+    }
+
+    #[test]
+    fn symbols_resolve_in_env() {
+        let mut env = Env::new();
+        env.set("x", Int(4));
+        env.set("half", Float(0.5));
+
+        assert_eq!(eval_in("x", &mut env).unwrap(), Value::Atom(Int(4)));
+        assert_eq!(
+            eval_in("(* x half)", &mut env).unwrap(),
+            Value::Atom(Float(2.0))
+        );
+    }
+
+    #[test]
+    fn unbound_symbol_err() {
+        assert_eq!(
+            eval_str("(+ 1 y)").unwrap_err(),
+            EvalError::UnboundSymbol("y".into())
+        );
+    }
+
+    #[test]
+    fn child_scopes() {
+        let mut parent = Env::new();
+        parent.set("x", Int(1));
+        parent.set("y", Int(2));
+
+        let mut child = parent.new_child();
+        child.set("x", Int(10));
+
+        assert_eq!(child.get("x"), Some(Value::Atom(Int(10))));
+        assert_eq!(child.get("y"), Some(Value::Atom(Int(2))));
+        assert_eq!(parent.get("x"), Some(Value::Atom(Int(1))));
+        assert_eq!(
+            eval_in("(+ x y)", &mut child).unwrap(),
+            Value::Atom(Int(12))
+        );
+
+        // The child sees later changes to its parent.
+        parent.set("y", Int(20));
+
+        assert_eq!(child.get("y"), Some(Value::Atom(Int(20))));
+    }
+
+    #[test]
+    fn set_overwrites() {
+        let mut env = Env::new();
+        env.set("x", Int(1));
+        env.set("x", Str("one".to_owned()));
+
+        assert_eq!(env.get("x"), Some(Value::Atom(Str("one".to_owned()))));
+        assert_eq!(env.get("missing"), None);
+    }
+
+    #[test]
+    fn int_arithmetic() {
+        assert_eq!(eval_str("(+ 1 2 3)").unwrap(), Value::Atom(Int(6)));
+        assert_eq!(eval_str("(- 10 4 3)").unwrap(), Value::Atom(Int(3)));
+        assert_eq!(eval_str("(* 2 3 4)").unwrap(), Value::Atom(Int(24)));
+        assert_eq!(eval_str("(/ 7 2)").unwrap(), Value::Atom(Int(3)));
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(eval_str("(+ (* 2 3) 4)").unwrap(), Value::Atom(Int(10)));
+        assert_eq!(
+            eval_str("(- (/ 20 (+ 1 1)) (* 2 (- 3)))").unwrap(),
+            Value::Atom(Int(16))
+        );
+    }
+
+    #[test]
+    fn float_promotion() {
+        assert_eq!(eval_str("(+ 1 2.5)").unwrap(), Value::Atom(Float(3.5)));
+        assert_eq!(eval_str("(/ 7 2.0)").unwrap(), Value::Atom(Float(3.5)));
+        assert_eq!(
+            eval_str("(* 2 (+ 1 0.5))").unwrap(),
+            Value::Atom(Float(3.0))
+        );
+    }
+
+    #[test]
+    fn numeric_policies() {
+        let eval_with = |code, policy| eval_in(code, &mut Env::with_policy(policy)).unwrap();
+
+        assert_eq!(
+            eval_with("(+ 1 2.0)", NumericPolicy::PromoteOnMix),
+            Value::Atom(Float(3.0))
+        );
+        assert_eq!(
+            eval_with("(+ 1 2.0)", NumericPolicy::AlwaysFloat),
+            Value::Atom(Float(3.0))
+        );
+        assert_eq!(
+            eval_with("(+ 1 2)", NumericPolicy::AlwaysFloat),
+            Value::Atom(Float(3.0))
+        );
+        assert_eq!(
+            eval_with("(/ 1 2)", NumericPolicy::PromoteOnMix),
+            Value::Atom(Int(0))
+        );
+        assert_eq!(
+            eval_with("(/ 1 2)", NumericPolicy::AlwaysFloat),
+            Value::Atom(Float(0.5))
+        );
+        assert_eq!(
+            eval_with("(/ 1 2)", NumericPolicy::ExactRational),
+            Value::Atom(Rational { num: 1, den: 2 })
+        );
+        assert_eq!(
+            eval_with("(+ 1/2 1/3 (/ -6))", NumericPolicy::ExactRational),
+            Value::Atom(Rational { num: 2, den: 3 })
+        );
+        assert_eq!(
+            eval_with("(* 3/2 4)", NumericPolicy::ExactRational),
+            Value::Atom(Int(6))
+        );
+        assert_eq!(
+            eval_with("(+ 1/2 0.25)", NumericPolicy::ExactRational),
+            Value::Atom(Float(0.75))
+        );
+        assert_eq!(
+            eval_with("(+ 1/2 1)", NumericPolicy::PromoteOnMix),
+            Value::Atom(Float(1.5))
+        );
+    }
+
+    #[test]
+    fn policy_carries_into_closures() {
+        let mut env = Env::with_policy(NumericPolicy::ExactRational);
+
+        eval_in("(define half (lambda (x) (/ x 2)))", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(half 3)", &mut env).unwrap(),
+            Value::Atom(Rational { num: 3, den: 2 })
+        );
+        assert_eq!(Env::new().policy(), NumericPolicy::PromoteOnMix);
+    }
+
+    #[test]
+    fn identities_and_unary_forms() {
+        assert_eq!(eval_str("(+)").unwrap(), Value::Atom(Int(0)));
+        assert_eq!(eval_str("(*)").unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_str("(- 5)").unwrap(), Value::Atom(Int(-5)));
+        assert_eq!(eval_str("(/ 4.0)").unwrap(), Value::Atom(Float(0.25)));
+        assert_eq!(
+            eval_str("(-)").unwrap_err(),
+            EvalError::WrongArity("-".to_owned())
+        );
+    }
+
+    #[test]
+    fn division_by_zero_err() {
+        assert_eq!(eval_str("(/ 1 0)").unwrap_err(), EvalError::DivisionByZero);
+        assert_eq!(
+            eval_str("(/ 1.5 0.0)").unwrap_err(),
+            EvalError::DivisionByZero
+        );
+        assert_eq!(eval_str("(/ 0)").unwrap_err(), EvalError::DivisionByZero);
+    }
+
+    #[test]
+    fn unknown_operator_err() {
+        assert_eq!(
+            eval_str("(% 1 2)").unwrap_err(),
+            EvalError::UnknownOperator("%".to_owned())
+        );
+        assert_eq!(
+            eval_str("(1 2)").unwrap_err(),
+            EvalError::UnknownOperator("1".to_owned())
+        );
+    }
+
+    #[test]
+    fn invalid_operands_err() {
+        assert_eq!(
+            eval_str("(+ 1 \"two\")").unwrap_err(),
+            EvalError::NotANumber("\"two\"".to_owned())
+        );
+        assert_eq!(
+            eval_str("()").unwrap_err(),
+            EvalError::InvalidCall("()".to_owned())
+        );
+        assert_eq!(
+            eval_str("(+ . 1)").unwrap_err(),
+            EvalError::InvalidCall("(+ . 1)".to_owned())
+        );
+    }
+
+    #[test]
+    fn overflow_err() {
+        let code = format!("(+ {} 1)", i64::MAX);
+
+        assert_eq!(eval_str(&code).unwrap_err(), EvalError::Overflow);
+    }
+
+    #[test]
+    fn define() {
+        let mut env = Env::new();
+
+        assert_eq!(
+            eval_in("(define x 42)", &mut env).unwrap(),
+            Value::Atom(Symbol("x".into()))
+        );
+        assert_eq!(
+            eval_in("(define y (+ x 1))", &mut env).unwrap(),
+            Value::Atom(Symbol("y".into()))
+        );
+        assert_eq!(eval_in("x", &mut env).unwrap(), Value::Atom(Int(42)));
+        assert_eq!(
+            eval_in("(* x y)", &mut env).unwrap(),
+            Value::Atom(Int(1806))
+        );
+    }
+
+    #[test]
+    fn redefine_overwrites() {
+        let mut env = Env::new();
+
+        eval_in("(define x 1)", &mut env).unwrap();
+        eval_in("(define x (+ x 1))", &mut env).unwrap();
+
+        assert_eq!(eval_in("x", &mut env).unwrap(), Value::Atom(Int(2)));
+    }
+
+    #[test]
+    fn define_in_child_scope_shadows() {
+        let mut parent = Env::new();
+        let mut child = parent.new_child();
+
+        eval_in("(define x 1)", &mut parent).unwrap();
+        eval_in("(define x 2)", &mut child).unwrap();
+
+        assert_eq!(eval_in("x", &mut parent).unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_in("x", &mut child).unwrap(), Value::Atom(Int(2)));
+    }
+
+    #[test]
+    fn malformed_define_err() {
+        for form in &["(define)", "(define x)", "(define 1 2)", "(define x 1 2)"] {
+            assert_eq!(
+                eval_str(form).unwrap_err(),
+                EvalError::MalformedForm((*form).to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn if_branches() {
+        assert_eq!(eval_str("(if #t 1 2)").unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_str("(if #f 1 2)").unwrap(), Value::Atom(Int(2)));
+        assert_eq!(eval_str("(if (+ 0 0) 1 2)").unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_str("(if \"\" 1 2)").unwrap(), Value::Atom(Int(1)));
+    }
+
+    #[test]
+    fn if_without_else() {
+        assert_eq!(eval_str("(if #f 1)").unwrap(), Value::Nil);
+        assert_eq!(eval_str("(if #t 1)").unwrap(), Value::Atom(Int(1)));
+    }
+
+    #[test]
+    fn nil_atom() {
+        let nil = parse_sexpr_with("nil", &ParseOptions::new().distinct_nil(true)).unwrap();
+
+        assert_eq!(eval(&nil, &mut Env::new()).unwrap(), Value::Nil);
+        assert!(matches!(eval_str("nil"), Err(EvalError::UnboundSymbol(_))));
+    }
+
+    #[test]
+    fn radix_ints_evaluate_to_ints() {
+        let sum = parse_sexpr_with("(+ #xFF #b1)", &ParseOptions::new().keep_radix(true)).unwrap();
+
+        assert_eq!(eval(&sum, &mut Env::new()).unwrap(), Value::Atom(Int(256)));
+    }
+
+    #[test]
+    fn if_only_evaluates_taken_branch() {
+        let mut env = Env::new();
+
+        assert_eq!(
+            eval_in("(if #t (define x 1) (define y 2))", &mut env).unwrap(),
+            Value::Atom(Symbol("x".into()))
+        );
+        assert_eq!(env.get("y"), None);
+        assert_eq!(eval_str("(if #f (/ 1 0) 3)").unwrap(), Value::Atom(Int(3)));
+        assert_eq!(
+            eval_str("(if #t 3 undefined)").unwrap(),
+            Value::Atom(Int(3))
+        );
+    }
+
+    #[test]
+    fn malformed_if_err() {
+        for form in &["(if)", "(if #t)", "(if #t 1 2 3)"] {
+            assert_eq!(
+                eval_str(form).unwrap_err(),
+                EvalError::MalformedForm((*form).to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn immediately_applied_lambda() {
+        assert_eq!(
+            eval_str("((lambda (x) (* x x)) 5)").unwrap(),
+            Value::Atom(Int(25))
+        );
+        assert_eq!(eval_str("((lambda () 1))").unwrap(), Value::Atom(Int(1)));
+    }
+
+    #[test]
+    fn defined_lambda() {
+        let mut env = Env::new();
+
+        eval_in("(define add (lambda (x y) (+ x y)))", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("add", &mut env).unwrap().to_string(),
+            "#<procedure>"
+        );
+        assert_eq!(eval_in("(add 2 3)", &mut env).unwrap(), Value::Atom(Int(5)));
+        assert_eq!(
+            eval_in("(add (add 1 2) 3)", &mut env).unwrap(),
+            Value::Atom(Int(6))
+        );
+    }
+
+    #[test]
+    fn closures_capture_their_scope() {
+        let mut env = Env::new();
+
+        eval_in(
+            "(define make-adder (lambda (n) (lambda (x) (+ x n))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_in("(define add-two (make-adder 2))", &mut env).unwrap();
+        eval_in("(define n 100)", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(add-two 5)", &mut env).unwrap(),
+            Value::Atom(Int(7))
+        );
+        assert_eq!(
+            eval_in("((make-adder 10) n)", &mut env).unwrap(),
+            Value::Atom(Int(110))
+        );
+    }
+
+    #[test]
+    fn closures_see_later_definitions() {
+        let mut env = Env::new();
+
+        eval_in("(define f (lambda () y))", &mut env).unwrap();
+        eval_in("(define y 3)", &mut env).unwrap();
+
+        assert_eq!(eval_in("(f)", &mut env).unwrap(), Value::Atom(Int(3)));
+    }
+
+    #[test]
+    fn params_shadow_and_operators_can_be_rebound() {
+        let mut env = Env::new();
+
+        eval_in("(define x 1)", &mut env).unwrap();
+        eval_in("(define + (lambda (a b) (* a b)))", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("((lambda (x) x) 2)", &mut env).unwrap(),
+            Value::Atom(Int(2))
+        );
+        assert_eq!(eval_in("x", &mut env).unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_in("(+ 3 4)", &mut env).unwrap(), Value::Atom(Int(12)));
+    }
+
+    #[test]
+    fn body_evaluates_in_order() {
+        let mut env = Env::new();
+
+        assert_eq!(
+            eval_in("((lambda (x) (define y (* x 2)) (+ y 1)) 4)", &mut env).unwrap(),
+            Value::Atom(Int(9))
+        );
+        // The definition was made in the call's own scope.
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn closure_call_errs() {
+        let mut env = Env::new();
+
+        eval_in("(define id (lambda (x) x))", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(id)", &mut env).unwrap_err(),
+            EvalError::WrongArity("id".to_owned())
+        );
+        assert_eq!(
+            eval_in("(id 1 2)", &mut env).unwrap_err(),
+            EvalError::WrongArity("id".to_owned())
+        );
+
+        eval_in("(define x 1)", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(x 2)", &mut env).unwrap_err(),
+            EvalError::UnknownOperator("x".to_owned())
+        );
+    }
+
+    #[test]
+    fn malformed_lambda_err() {
+        for form in &["(lambda)", "(lambda (x))", "(lambda x x)", "(lambda (1) 1)"] {
+            assert_eq!(
+                eval_str(form).unwrap_err(),
+                EvalError::MalformedForm((*form).to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn closures_equal_when_same_code_and_env() {
+        let mut env = Env::new();
+        let a = eval_in("(lambda (x) x)", &mut env).unwrap();
+        let b = eval_in("(lambda (x) x)", &mut env).unwrap();
+
+        assert_eq!(a, a.clone());
+        assert_eq!(a, b);
+        assert_ne!(a, eval_in("(lambda (y) y)", &mut env).unwrap());
+        assert_ne!(a, eval_in("(lambda (x) x)", &mut env.new_child()).unwrap());
+    }
+
+    #[test]
+    fn let_bindings() {
+        assert_eq!(
+            eval_str("(let ((x 1) (y 2)) (+ x y))").unwrap(),
+            Value::Atom(Int(3))
+        );
+        assert_eq!(eval_str("(let () 4)").unwrap(), Value::Atom(Int(4)));
+        assert_eq!(
+            eval_str("(let ((x 2)) (define y 3) (* x y))").unwrap(),
+            Value::Atom(Int(6))
+        );
+    }
+
+    #[test]
+    fn let_scope_is_discarded() {
+        let mut env = Env::new();
+
+        eval_in("(define x 1)", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(let ((x 10) (y 2)) (define z 3) (+ x y z))", &mut env).unwrap(),
+            Value::Atom(Int(15))
+        );
+        assert_eq!(eval_in("x", &mut env).unwrap(), Value::Atom(Int(1)));
+        assert_eq!(env.get("y"), None);
+        assert_eq!(env.get("z"), None);
+    }
+
+    #[test]
+    fn let_values_see_outer_scope() {
+        let mut env = Env::new();
+
+        eval_in("(define x 1)", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(let ((x 10) (y x)) y)", &mut env).unwrap(),
+            Value::Atom(Int(1))
+        );
+        assert_eq!(
+            eval_str("(let ((x 1) (y x)) y)").unwrap_err(),
+            EvalError::UnboundSymbol("x".into())
+        );
+    }
+
+    #[test]
+    fn let_star_values_see_earlier_bindings() {
+        let mut env = Env::new();
+
+        eval_in("(define x 1)", &mut env).unwrap();
+
+        assert_eq!(
+            eval_in("(let* ((x 10) (y x)) y)", &mut env).unwrap(),
+            Value::Atom(Int(10))
+        );
+        assert_eq!(
+            eval_str("(let* ((x 1) (y (+ x 1)) (z (* y 2))) z)").unwrap(),
+            Value::Atom(Int(4))
+        );
+    }
+
+    #[test]
+    fn let_closures_capture_bindings() {
+        let mut env = Env::new();
+
+        eval_in("(define f (let ((n 5)) (lambda (x) (+ x n))))", &mut env).unwrap();
+
+        assert_eq!(eval_in("(f 1)", &mut env).unwrap(), Value::Atom(Int(6)));
+    }
+
+    #[test]
+    fn malformed_let_err() {
+        let forms = [
+            "(let)",
+            "(let ((x 1)))",
+            "(let x 1)",
+            "(let (x) 1)",
+            "(let ((x)) 1)",
+            "(let ((x 1 2)) 1)",
+            "(let ((1 2)) 1)",
+            "(let* ((x . 1)) x)",
+        ];
+
+        for form in forms.iter() {
+            assert_eq!(
+                eval_str(form).unwrap_err(),
+                EvalError::MalformedForm((*form).to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        let cases = [
+            ("(< 1 2)", true),
+            ("(< 2 1)", false),
+            ("(> 2 1)", true),
+            ("(<= 1 1)", true),
+            ("(>= 1 2)", false),
+            ("(= 3 3)", true),
+            ("(= 3 4)", false),
+            ("(< 1 2 3)", true),
+            ("(< 1 3 2)", false),
+            ("(= 1 1 1)", true),
+            ("(= 5)", true),
+        ];
+
+        for (code, expected) in cases.iter() {
+            assert_eq!(
+                eval_str(code).unwrap(),
+                Value::Atom(Bool(*expected)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn comparisons_promote_ints() {
+        assert_eq!(eval_str("(= 1 1.0)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(
+            eval_str("(= 9007199254740993 9007199254740992.0)").unwrap(),
+            Value::Atom(Bool(false)),
+        );
+        assert_eq!(eval_str("(< 1 1.5 2)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(eval_str("(>= 2.0 2)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(eval_str("(> 0.5 1)").unwrap(), Value::Atom(Bool(false)));
+    }
+
+    #[test]
+    fn comparisons_are_exact_near_float_precision() {
+        // 2^53 + 1, which no float can hold, against 2^53.
+        let cases = [
+            ("(= 9007199254740993 9007199254740992.0)", false),
+            ("(<= 9007199254740993 9007199254740992.0)", false),
+            ("(>= 9007199254740993 9007199254740992.0)", true),
+            ("(> 9007199254740993 9007199254740992.0)", true),
+            ("(< 9007199254740992.0 9007199254740993)", true),
+            ("(<= 9007199254740992 9007199254740992.0)", true),
+            ("(>= 9007199254740992 9007199254740992.0)", true),
+            ("(< 9223372036854775807 9223372036854775808.0)", true),
+            ("(> -9223372036854775808 -1e19)", true),
+            ("(< 2 2.5 3)", true),
+            ("(< -3 -2.5 -2)", true),
+            ("(< 1/3 3074457345618258603/9223372036854775807)", true),
+        ];
+
+        for (code, expected) in cases.iter() {
+            assert_eq!(
+                eval_str(code).unwrap(),
+                Value::Atom(Bool(*expected)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn comparison_errs() {
+        assert_eq!(
+            eval_str("(<)").unwrap_err(),
+            EvalError::WrongArity("<".to_owned())
+        );
+        assert_eq!(
+            eval_str("(= 1 #t)").unwrap_err(),
+            EvalError::NotANumber("#t".to_owned())
+        );
+    }
+
+    #[test]
+    fn not() {
+        assert_eq!(eval_str("(not #f)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(eval_str("(not #t)").unwrap(), Value::Atom(Bool(false)));
+        assert_eq!(eval_str("(not 0)").unwrap(), Value::Atom(Bool(false)));
+        assert_eq!(
+            eval_str("(not 1 2)").unwrap_err(),
+            EvalError::WrongArity("not".to_owned())
+        );
+    }
+
+    #[test]
+    fn and_or() {
+        assert_eq!(eval_str("(and #t #f)").unwrap(), Value::Atom(Bool(false)));
+        assert_eq!(
+            eval_str("(and #t 1 (< 1 2))").unwrap(),
+            Value::Atom(Bool(true))
+        );
+        assert_eq!(eval_str("(or #f #t)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(
+            eval_str("(or #f (> 1 2))").unwrap(),
+            Value::Atom(Bool(false))
+        );
+        assert_eq!(eval_str("(and)").unwrap(), Value::Atom(Bool(true)));
+        assert_eq!(eval_str("(or)").unwrap(), Value::Atom(Bool(false)));
+    }
+
+    #[test]
+    fn and_or_short_circuit() {
+        assert_eq!(
+            eval_str("(and #f (/ 1 0))").unwrap(),
+            Value::Atom(Bool(false))
+        );
+        assert_eq!(
+            eval_str("(or 1 undefined)").unwrap(),
+            Value::Atom(Bool(true))
+        );
+        assert_eq!(
+            eval_str("(and #t (/ 1 0))").unwrap_err(),
+            EvalError::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn conditional_logic() {
+        let mut env = Env::new();
+
+        eval_in(
+            "(define clamp (lambda (x lo hi) (if (< x lo) lo (if (> x hi) hi x))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_in("(clamp -5 0 10)", &mut env).unwrap(),
+            Value::Atom(Int(0))
+        );
+        assert_eq!(
+            eval_in("(clamp 5 0 10)", &mut env).unwrap(),
+            Value::Atom(Int(5))
+        );
+        assert_eq!(
+            eval_in("(clamp 50 0 10)", &mut env).unwrap(),
+            Value::Atom(Int(10))
+        );
+    }
+
+    #[test]
+    fn list_primitives() {
+        let list = |values: Vec<i64>| {
+            Value::List(values.into_iter().map(|i| Value::Atom(Int(i))).collect())
+        };
+
+        assert_eq!(eval_str("(list 1 2 3)").unwrap(), list(vec![1, 2, 3]));
+        assert_eq!(eval_str("(list)").unwrap(), list(vec![]));
+        assert_eq!(eval_str("(car (list 1 2 3))").unwrap(), Value::Atom(Int(1)));
+        assert_eq!(eval_str("(cdr (list 1 2 3))").unwrap(), list(vec![2, 3]));
+        assert_eq!(eval_str("(cdr (list 1))").unwrap(), list(vec![]));
+        assert_eq!(
+            eval_str("(cons 1 (list 2 3))").unwrap(),
+            list(vec![1, 2, 3])
+        );
+        assert_eq!(eval_str("(cons 1 (list))").unwrap(), list(vec![1]));
+    }
+
+    #[test]
+    fn nested_lists() {
+        assert_eq!(
+            eval_str("(list 1 (list 2 (+ 1 2)) \"four\")")
+                .unwrap()
+                .to_string(),
+            "(1 (2 3) \"four\")"
+        );
+        assert_eq!(
+            eval_str("(car (cdr (car (cdr (list 1 (list 2 3))))))").unwrap(),
+            Value::Atom(Int(3))
+        );
+    }
+
+    #[test]
+    fn recursion_over_lists() {
+        let mut env = Env::new();
+
+        eval_in(
+            "(define nth (lambda (xs n) (if (= n 0) (car xs) (nth (cdr xs) (- n 1)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_in("(nth (list 10 20 30) 2)", &mut env).unwrap(),
+            Value::Atom(Int(30))
+        );
+    }
+
+    #[test]
+    fn list_errs() {
+        assert_eq!(
+            eval_str("(car (list))").unwrap_err(),
+            EvalError::EmptyList("car".to_owned())
+        );
+        assert_eq!(
+            eval_str("(cdr (list))").unwrap_err(),
+            EvalError::EmptyList("cdr".to_owned())
+        );
+        assert_eq!(
+            eval_str("(car 1)").unwrap_err(),
+            EvalError::NotAList("1".to_owned())
+        );
+        assert_eq!(
+            eval_str("(cons 1 2)").unwrap_err(),
+            EvalError::NotAList("2".to_owned())
+        );
+        assert_eq!(
+            eval_str("(car (list 1) (list 2))").unwrap_err(),
+            EvalError::WrongArity("car".to_owned())
+        );
+        assert_eq!(
+            eval_str("(cons 1)").unwrap_err(),
+            EvalError::WrongArity("cons".to_owned())
+        );
+    }
+
+    #[test]
+    fn deep_tail_recursion() {
+        let mut env = Env::new();
+
+        eval_in(
+            "(define count (lambda (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_in("(count 100000 0)", &mut env).unwrap(),
+            Value::Atom(Int(100000))
+        );
+    }
+
+    #[test]
+    fn deep_nesting_on_a_small_stack() {
+        let nested = |depth| format!("{}1{}", "(+ 1 ".repeat(depth), ")".repeat(depth));
+
+        std::thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                assert_eq!(
+                    eval_str(&nested(DEFAULT_EVAL_DEPTH_LIMIT - 1)),
+                    Ok(Value::Atom(Int(DEFAULT_EVAL_DEPTH_LIMIT as i64)))
+                );
+                assert_eq!(
+                    eval_str(&nested(DEFAULT_EVAL_DEPTH_LIMIT)),
+                    Err(EvalError::DepthLimitExceeded)
+                );
+                assert_eq!(eval_str(&nested(511)), Err(EvalError::DepthLimitExceeded));
+
+                let mut env = Env::new();
+
+                eval_in(
+                    "(define depth (lambda (n) (if (= n 0) 0 (+ 1 (depth (- n 1))))))",
+                    &mut env,
+                )
+                .unwrap();
+
+                assert_eq!(eval_in("(depth 50)", &mut env), Ok(Value::Atom(Int(50))));
+                assert_eq!(
+                    eval_in("(depth 100000)", &mut env),
+                    Err(EvalError::DepthLimitExceeded)
+                );
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn depth_limit_can_be_changed() {
+        let mut env = Env::new();
+
+        env.set_depth_limit(3);
+        assert_eq!(env.new_child().depth_limit(), 3);
+        assert_eq!(eval_in("(+ 1 (+ 1 1))", &mut env), Ok(Value::Atom(Int(3))));
+        assert_eq!(
+            eval_in("(+ 1 (+ 1 (+ 1 1)))", &mut env),
+            Err(EvalError::DepthLimitExceeded)
+        );
+
+        std::thread::Builder::new()
+            .stack_size(256 * 1024 * 1024)
+            .spawn(|| {
+                let mut env = Env::new();
+
+                env.set_depth_limit(100_000);
+                eval_in(
+                    "(define depth (lambda (n) (if (= n 0) 0 (+ 1 (depth (- n 1))))))",
+                    &mut env,
+                )
+                .unwrap();
+
+                assert_eq!(
+                    eval_in("(depth 10000)", &mut env),
+                    Ok(Value::Atom(Int(10000)))
+                );
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn tail_calls_through_special_forms() {
+        let mut env = Env::new();
+
+        eval_in(
+            "(define even? (lambda (n) (if (= n 0) #t (let ((m (- n 1))) (odd? m)))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_in(
+            "(define odd? (lambda (n) (if (= n 0) #f (let* () (even? (- n 1))))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_in("(even? 100001)", &mut env).unwrap(),
+            Value::Atom(Bool(false))
+        );
+        assert_eq!(
+            eval_in("(odd? 100001)", &mut env).unwrap(),
+            Value::Atom(Bool(true))
+        );
+    }
+}
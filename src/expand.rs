@@ -0,0 +1,311 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{
+    parser::{Atom, Node},
+    symbol::Symbol,
+};
+
+/// How many expansions deep `macroexpand` may go before giving up, counting
+/// those of a form and of every form around it, so that a macro which
+/// expands into itself, directly or nested inside its expansion, is an
+/// error rather than a hang or a stack overflow. Nested expansions recurse,
+/// so this is kept low for the reasons given at `DEFAULT_EVAL_DEPTH_LIMIT`.
+const EXPANSION_LIMIT: usize = 256;
+
+/// A set of macros for `macroexpand`, each keyed by the symbol at the head of
+/// the forms it rewrites.
+#[derive(Debug, Clone, Default)]
+pub struct MacroTable {
+    macros: HashMap<Symbol, Macro>,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<Symbol>,
+    template: Node,
+}
+
+impl MacroTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a macro that rewrites `(name args...)` into a copy of
+    /// `template` with each of `params` replaced by the matching argument,
+    /// replacing any existing macro of the same name. The substitution is
+    /// purely textual, so symbols in the template are free to capture names
+    /// from the arguments and vice versa.
+    pub fn define(&mut self, name: impl Into<Symbol>, params: &[&str], template: Node) {
+        let params = params.iter().map(|&param| Symbol::new(param)).collect();
+
+        self.macros.insert(name.into(), Macro { params, template });
+    }
+
+    pub fn contains(&self, name: impl Into<Symbol>) -> bool {
+        self.macros.contains_key(&name.into())
+    }
+
+    /// Finds the macro a form calls, if it's a list headed by one.
+    fn lookup<'a>(&'a self, node: &'a Node) -> Option<(Symbol, &'a Macro, &'a [Node])> {
+        match node.as_list()? {
+            [Node::Atom(Atom::Symbol(head)), args @ ..] => {
+                Some((*head, self.macros.get(head)?, args))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rewrites every call to one of `macros` in `node`, the call itself first
+/// and then its result, until no calls are left. The contents of `quote`
+/// forms are data rather than code, so they're left alone.
+pub fn macroexpand(node: &Node, macros: &MacroTable) -> Result<Node, ExpandError> {
+    expand(node, macros, 0)
+}
+
+/// Expands `node` like `macroexpand`, where `expansions` is how many times
+/// the forms it came from have been expanded already.
+fn expand(node: &Node, macros: &MacroTable, mut expansions: usize) -> Result<Node, ExpandError> {
+    let mut node = node.clone();
+
+    while let Some((name, m, args)) = macros.lookup(&node) {
+        if args.len() != m.params.len() {
+            return Err(ExpandError::WrongArity(name.to_string()));
+        }
+
+        expansions += 1;
+
+        if expansions > EXPANSION_LIMIT {
+            return Err(ExpandError::ExpansionLimit(name.to_string()));
+        }
+
+        let bindings = m.params.iter().copied().zip(args).collect();
+
+        node = substitute(&m.template, &bindings);
+    }
+
+    match &node {
+        Node::List(_) if node.match_list("quote").is_some() => Ok(node),
+        Node::List(v) => Ok(Node::List(expand_all(v, macros, expansions)?)),
+        Node::Vector(v) => Ok(Node::Vector(expand_all(v, macros, expansions)?)),
+        Node::Pair(..) => {
+            // A dotted list can be far longer than the tree is deep, so its
+            // pairs are followed in a loop.
+            let mut cars = vec![];
+            let mut tail = &node;
+
+            while let Node::Pair(car, cdr) = tail {
+                cars.push(expand(car, macros, expansions)?);
+                tail = cdr;
+            }
+
+            let tail = expand(tail, macros, expansions)?;
+
+            Ok(cars
+                .into_iter()
+                .rev()
+                .fold(tail, |cdr, car| Node::Pair(Box::new(car), Box::new(cdr))))
+        }
+        Node::Atom(_) => Ok(node),
+    }
+}
+
+// A loop rather than `map` and `collect`, which take several stack frames
+// for each level of nesting rather than just the one.
+fn expand_all(
+    nodes: &[Node],
+    macros: &MacroTable,
+    expansions: usize,
+) -> Result<Vec<Node>, ExpandError> {
+    let mut expanded = Vec::with_capacity(nodes.len());
+
+    for node in nodes {
+        expanded.push(expand(node, macros, expansions)?);
+    }
+
+    Ok(expanded)
+}
+
+/// Hands out symbols named `g__1`, `g__2` and so on, for macros to bind names
+/// that won't clash with the code around them. Each generator counts on its
+/// own and needs `&mut` access to do so, so code sharing one between threads
+/// has to lock it, and two generators will hand out the same names. Nothing
+/// stops source code from using a name like `g__1` itself, so the symbols
+/// are only unique among those the generator has made.
+#[derive(Debug, Clone, Default)]
+pub struct Gensym {
+    count: u64,
+}
+
+impl Gensym {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a symbol this generator hasn't made before.
+    pub fn fresh(&mut self) -> Atom {
+        self.count += 1;
+
+        Atom::Symbol(Symbol::new(&format!("g__{}", self.count)))
+    }
+}
+
+fn substitute(template: &Node, bindings: &HashMap<Symbol, &Node>) -> Node {
+    match template {
+        Node::List(v) => Node::List(v.iter().map(|n| substitute(n, bindings)).collect()),
+        Node::Vector(v) => Node::Vector(v.iter().map(|n| substitute(n, bindings)).collect()),
+        Node::Pair(..) => {
+            let mut cars = vec![];
+            let mut tail = template;
+
+            while let Node::Pair(car, cdr) = tail {
+                cars.push(substitute(car, bindings));
+                tail = cdr;
+            }
+
+            cars.into_iter()
+                .rev()
+                .fold(substitute(tail, bindings), |cdr, car| {
+                    Node::Pair(Box::new(car), Box::new(cdr))
+                })
+        }
+        Node::Atom(Atom::Symbol(s)) => match bindings.get(s) {
+            Some(arg) => (*arg).clone(),
+            None => template.clone(),
+        },
+        Node::Atom(_) => template.clone(),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpandError {
+    /// A macro was called with a different number of arguments than it
+    /// takes.
+    WrongArity(String),
+    /// A macro kept expanding into another macro call without end.
+    ExpansionLimit(String),
+}
+
+impl Error for ExpandError {}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WrongArity(name) => write!(f, "Wrong number of arguments to macro {}", name),
+            Self::ExpansionLimit(name) => write!(f, "Macro {} expanded too many times", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{macroexpand, ExpandError, Gensym, MacroTable};
+    use crate::parser::{parse_sexpr, Atom};
+
+    fn when() -> MacroTable {
+        let mut macros = MacroTable::new();
+
+        macros.define("when", &["c", "a"], parse_sexpr("(if c a)").unwrap());
+        macros
+    }
+
+    fn expand(code: &str, macros: &MacroTable) -> Result<String, ExpandError> {
+        macroexpand(&parse_sexpr(code).unwrap(), macros).map(|node| node.to_string())
+    }
+
+    #[test]
+    fn when_expands_to_if() {
+        let macros = when();
+
+        assert_eq!(expand("(when c a)", &macros).unwrap(), "(if c a)");
+        assert_eq!(
+            expand("(when (> x 0) (print x))", &macros).unwrap(),
+            "(if (> x 0) (print x))"
+        );
+    }
+
+    #[test]
+    fn expands_nested_and_resulting_calls() {
+        let mut macros = when();
+
+        macros.define(
+            "unless",
+            &["c", "a"],
+            parse_sexpr("(when (not c) a)").unwrap(),
+        );
+
+        assert_eq!(
+            expand("(list (unless x (when y z)) '(when a b))", &macros).unwrap(),
+            "(list (if (not x) (if y z)) (quote (when a b)))"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut macros = when();
+
+        macros.define("forever", &[], parse_sexpr("(forever)").unwrap());
+
+        assert_eq!(
+            expand("(when c)", &macros),
+            Err(ExpandError::WrongArity("when".to_owned()))
+        );
+        assert_eq!(
+            expand("(forever)", &macros),
+            Err(ExpandError::ExpansionLimit("forever".to_owned()))
+        );
+    }
+
+    #[test]
+    fn self_nesting_macro_hits_the_limit() {
+        let mut macros = MacroTable::new();
+
+        macros.define("f", &[], parse_sexpr("(g (f))").unwrap());
+
+        assert_eq!(
+            expand("(f)", &macros),
+            Err(ExpandError::ExpansionLimit("f".to_owned()))
+        );
+    }
+
+    #[test]
+    fn expands_inside_dotted_lists() {
+        let macros = when();
+
+        assert_eq!(
+            expand("(a (when b c) . (when d e))", &macros).unwrap(),
+            "(a (if b c) . (if d e))"
+        );
+    }
+
+    #[test]
+    fn long_dotted_template_on_a_small_stack() {
+        let template = format!("({}. x)", "x ".repeat(100_000));
+        let expected = format!("({}. 1)", "1 ".repeat(100_000));
+
+        std::thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                let mut macros = MacroTable::new();
+
+                macros.define("m", &["x"], parse_sexpr(&template).unwrap());
+
+                assert_eq!(expand("(m 1)", &macros).unwrap(), expected);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn gensym_makes_distinct_symbols() {
+        let mut gensym = Gensym::new();
+        let first = gensym.fresh();
+        let second = gensym.fresh();
+
+        assert_ne!(first, second);
+        assert_eq!(first, Atom::Symbol("g__1".into()));
+        assert_eq!(second.to_string(), "g__2");
+    }
+}
@@ -0,0 +1,203 @@
+use serde_json::{Map, Number, Value};
+
+use crate::parser::{Atom, Float, Node};
+
+impl Node {
+    /// Converts the node into JSON data. Lists become arrays, except that a
+    /// non-empty list made up entirely of `("key" . value)` pairs becomes an
+    /// object, as long as no key appears twice. An object can only hold one
+    /// value for each key, so a list that repeats one stays an array of its
+    /// pairs rather than losing all but the last. Numbers, booleans and
+    /// strings map across directly.
+    ///
+    /// JSON has only one kind of string, so symbols are written as strings
+    /// with a leading `'`, as in `"'name"`, and keywords as `"':name"`. That
+    /// means a string literal that itself starts with `'` comes back from
+    /// `from_json` as a symbol. Some things have no JSON equivalent at all
+    /// and don't survive the trip back: characters become one-character
+    /// strings, pairs outside of objects become arrays ending in their tail,
+    /// vectors become plain arrays, and infinite or `NaN` floats become
+    /// `null`, as does `Atom::Nil`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Node::List(v) => match as_object(v) {
+                Some(object) => Value::Object(object),
+                None => Value::Array(v.iter().map(Node::to_json).collect()),
+            },
+            Node::Pair(..) => Value::Array(self.children().map(Node::to_json).collect()),
+            Node::Vector(v) => Value::Array(v.iter().map(Node::to_json).collect()),
+            Node::Atom(atom) => atom_to_json(atom),
+        }
+    }
+
+    /// Converts JSON data into a node, following the conventions described
+    /// in `to_json`. Objects become lists of `("key" . value)` pairs, and
+    /// `null` becomes the empty list.
+    pub fn from_json(value: &Value) -> Node {
+        match value {
+            Value::Null => Node::List(vec![]),
+            Value::Bool(b) => Node::bool(*b),
+            Value::Number(n) => Node::Atom(number_from_json(n)),
+            Value::String(s) => match s.strip_prefix('\'') {
+                Some(name) => match name.strip_prefix(':') {
+                    Some(keyword) => Node::Atom(Atom::Keyword(keyword.to_owned())),
+                    None => Node::symbol(name),
+                },
+                None => Node::string(s),
+            },
+            Value::Array(v) => v.iter().map(Node::from_json).collect(),
+            Value::Object(object) => object
+                .iter()
+                .map(|(key, value)| {
+                    Node::Pair(
+                        Box::new(Node::string(key)),
+                        Box::new(Node::from_json(value)),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Turns a list into an object if every element of it is a pair keyed by a
+/// string, and no two of them have the same key.
+fn as_object(elements: &[Node]) -> Option<Map<String, Value>> {
+    if elements.is_empty() {
+        return None;
+    }
+
+    let mut object = Map::new();
+
+    for element in elements {
+        let (key, value) = match element {
+            Node::Pair(key, value) => match &**key {
+                Node::Atom(Atom::Str(key)) => (key, value),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        if object.insert(key.clone(), value.to_json()).is_some() {
+            return None;
+        }
+    }
+
+    Some(object)
+}
+
+fn atom_to_json(atom: &Atom) -> Value {
+    match atom {
+        Atom::Int(i) | Atom::RadixInt(i, _) => Value::from(*i),
+        // Too large for a JSON number to hold exactly, so this is written
+        // out as a string of digits instead.
+        #[cfg(feature = "bigint")]
+        Atom::BigInt(i) => Value::String(i.to_string()),
+        Atom::Float(f) => Value::from(*f),
+        // Writing this as a float would lose its exactness, so like a
+        // `BigInt` it's written out as a string.
+        Atom::Rational { num, den } => Value::String(format!("{}/{}", num, den)),
+        Atom::Symbol(s) => Value::String(format!("'{}", s.as_str())),
+        Atom::Keyword(k) => Value::String(format!("':{}", k)),
+        Atom::Str(s) => Value::String(s.clone()),
+        Atom::Char(c) => Value::String(c.to_string()),
+        Atom::Bool(b) => Value::Bool(*b),
+        Atom::Nil => Value::Null,
+    }
+}
+
+fn number_from_json(n: &Number) -> Atom {
+    match n.as_i64() {
+        Some(i) => Atom::Int(i),
+        // Every JSON number that isn't an integer is representable as an
+        // `f64`, possibly with some loss of precision, and more of it if
+        // that then has to be narrowed to an `f32`.
+        _ => Atom::Float(n.as_f64().unwrap_or(f64::NAN) as Float),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::parser::{parse_sexpr, Atom, Float, Node};
+
+    #[test]
+    fn lists_and_atoms() {
+        let node = parse_sexpr("(define x (list 1 2.5 \"three\" #t :four))").unwrap();
+
+        assert_eq!(
+            node.to_json(),
+            json!(["'define", "'x", ["'list", 1, 2.5, "three", true, "':four"]]),
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let samples = [
+            "(car (list 1 (+ 2 3) (* (+ 4 5) 6)))",
+            "(\"a string\" a-symbol :keyword -7 0.5 #f ())",
+            "((\"name\" . \"lisp\") (\"tags\" . ((\"x\" . 1))))",
+        ];
+
+        for sample in samples.iter() {
+            let node = parse_sexpr(sample).unwrap();
+
+            assert_eq!(Node::from_json(&node.to_json()), node, "{}", sample);
+        }
+    }
+
+    #[test]
+    fn objects_are_association_lists() {
+        let value = json!({"name": "lisp", "version": 1, "nested": {"ok": true}});
+        let node = Node::from_json(&value);
+
+        assert_eq!(
+            node,
+            parse_sexpr("((\"name\" . \"lisp\") (\"nested\" . ((\"ok\" . #t))) (\"version\" . 1))")
+                .unwrap(),
+        );
+        assert_eq!(node.to_json(), value);
+    }
+
+    #[test]
+    fn repeated_keys_stay_an_array() {
+        assert_eq!(
+            parse_sexpr("((\"k\" . 1) (\"j\" . 2) (\"k\" . 3))")
+                .unwrap()
+                .to_json(),
+            json!([["k", 1], ["j", 2], ["k", 3]]),
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Node::from_json(&json!(42)), Node::int(42));
+        assert_eq!(Node::from_json(&json!(-1.25)), Node::float(-1.25));
+        assert_eq!(
+            Node::from_json(&json!(u64::MAX)),
+            Node::float(u64::MAX as Float)
+        );
+    }
+
+    #[test]
+    fn symbol_string_ambiguity() {
+        assert_eq!(Node::from_json(&json!("'quoted")), Node::symbol("quoted"));
+        assert_eq!(Node::from_json(&json!("plain")), Node::string("plain"));
+        assert_eq!(
+            Node::from_json(&Node::string("'looks like a symbol").to_json()),
+            Node::symbol("looks like a symbol"),
+        );
+    }
+
+    #[test]
+    fn lossy_conversions() {
+        assert_eq!(Node::Atom(Atom::Char('c')).to_json(), json!("c"));
+        assert_eq!(
+            parse_sexpr("(a b . c)").unwrap().to_json(),
+            json!(["'a", "'b", "'c"])
+        );
+        assert_eq!(Node::float(Float::NAN).to_json(), json!(null));
+        assert_eq!(Node::from_json(&json!(null)), Node::list([]));
+        assert_eq!(Node::list([]).to_json(), json!([]));
+    }
+}
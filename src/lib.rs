@@ -0,0 +1,40 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "miette")]
+mod diagnostic;
+#[cfg(feature = "std")]
+mod eval;
+#[cfg(feature = "std")]
+mod expand;
+#[cfg(feature = "json")]
+mod json;
+mod lint;
+mod parser;
+pub mod prelude;
+mod pretty;
+mod shared;
+mod symbol;
+mod trivia;
+mod visit;
+
+#[cfg(feature = "std")]
+pub use eval::{eval, Env, EvalError, NumericPolicy, Value, DEFAULT_EVAL_DEPTH_LIMIT};
+#[cfg(feature = "std")]
+pub use expand::{macroexpand, ExpandError, Gensym, MacroTable};
+pub use lint::{lint, LintWarning};
+#[cfg(feature = "std")]
+pub use parser::parse_from_reader;
+pub use parser::{
+    is_complete, is_complete_with, parse_one, parse_one_with, parse_program, parse_program_recover,
+    parse_program_recover_with, parse_program_spanned, parse_program_spanned_with,
+    parse_program_with, parse_sexpr, parse_sexpr_spanned, parse_sexpr_spanned_with,
+    parse_sexpr_with, parse_sexpr_with_max_depth, tokenize, Atom, Float, Node, ParseOptions,
+    Position, Radix, SexprSyntaxError, Spans, Token, TokenKind, Tokenizer, DEFAULT_MAX_DEPTH,
+};
+pub use pretty::{DisplayStyle, PrettyPrinter};
+pub use shared::SharedNode;
+pub use symbol::Symbol;
+pub use trivia::{parse_program_with_comments, Comment, Trivia};
+pub use visit::Visitor;
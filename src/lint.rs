@@ -0,0 +1,150 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::parser::{Atom, Node};
+
+/// A special form `lint` knows the shape of.
+struct Form {
+    name: &'static str,
+    min_args: usize,
+    max_args: Option<usize>,
+    /// Whether the first argument has to be a list, like the parameters of a
+    /// `lambda` or the bindings of a `let`.
+    list_first: bool,
+}
+
+const FORMS: &[Form] = &[
+    Form {
+        name: "define",
+        min_args: 2,
+        max_args: Some(2),
+        list_first: false,
+    },
+    Form {
+        name: "if",
+        min_args: 2,
+        max_args: Some(3),
+        list_first: false,
+    },
+    Form {
+        name: "lambda",
+        min_args: 2,
+        max_args: None,
+        list_first: true,
+    },
+    Form {
+        name: "let",
+        min_args: 2,
+        max_args: None,
+        list_first: true,
+    },
+    Form {
+        name: "let*",
+        min_args: 2,
+        max_args: None,
+        list_first: true,
+    },
+    Form {
+        name: "quote",
+        min_args: 1,
+        max_args: Some(1),
+        list_first: false,
+    },
+];
+
+/// Looks through `node` for special forms that are obviously malformed,
+/// such as an `if` without a branch or a `lambda` without a parameter list,
+/// without evaluating anything. The contents of `quote` forms are data, so
+/// they aren't checked.
+pub fn lint(node: &Node) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    lint_node(node, &mut warnings);
+    warnings
+}
+
+fn lint_node(node: &Node, warnings: &mut Vec<LintWarning>) {
+    if let Some([Node::Atom(Atom::Symbol(head)), args @ ..]) = node.as_list() {
+        if let Some(form) = FORMS.iter().find(|form| *head == form.name) {
+            let too_many = form.max_args.is_some_and(|max| args.len() > max);
+
+            if args.len() < form.min_args || too_many {
+                warnings.push(LintWarning::WrongArity(node.to_string()));
+            } else if form.list_first && args[0].as_list().is_none() {
+                warnings.push(LintWarning::ExpectedList(node.to_string()));
+            }
+
+            if form.name == "quote" {
+                return;
+            }
+        }
+    }
+
+    for child in node.children() {
+        lint_node(child, warnings);
+    }
+}
+
+/// A problem `lint` found, with the form it was found in.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum LintWarning {
+    /// A special form with too few or too many arguments.
+    WrongArity(String),
+    /// A special form whose first argument should have been a list.
+    ExpectedList(String),
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WrongArity(form) => write!(f, "Wrong number of arguments in {}", form),
+            Self::ExpectedList(form) => write!(f, "Expected a list to start {}", form),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, LintWarning};
+    use crate::parser::parse_sexpr;
+
+    fn lint_str(code: &str) -> Vec<LintWarning> {
+        lint(&parse_sexpr(code).unwrap())
+    }
+
+    #[test]
+    fn well_formed() {
+        assert_eq!(lint_str("(if a b c)"), []);
+        assert_eq!(lint_str("(define f (lambda (x) (let ((y x)) y)))"), []);
+        assert_eq!(lint_str("(f if)"), []);
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            lint_str("(if 1)"),
+            [LintWarning::WrongArity("(if 1)".to_owned())]
+        );
+        assert_eq!(
+            lint_str("(lambda x x)"),
+            [LintWarning::ExpectedList("(lambda x x)".to_owned())]
+        );
+        assert_eq!(
+            lint_str("(list (lambda) (define x 1 2))"),
+            [
+                LintWarning::WrongArity("(lambda)".to_owned()),
+                LintWarning::WrongArity("(define x 1 2)".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_forms_are_data() {
+        assert_eq!(lint_str("'(if)"), []);
+        assert_eq!(lint_str("(quote (if) (if))").len(), 1);
+    }
+}
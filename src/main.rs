@@ -1,16 +1,258 @@
-use std::{error::Error, io::{self, Read}};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, BufRead, Read, Write},
+    process,
+};
 
-mod parser;
-use parser::parse_sexpr;
+use lisp_rs::{is_complete_with, prelude::*, Visitor};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut program = String::new();
-    io::stdin().read_to_string(&mut program)?;
+const USAGE: &str = "Usage: lisp-rs [--json | --stats] [FILE]";
 
-    let ast = parse_sexpr(&program)?;
+/// Runs the program in the file named by the first argument, or starts a
+/// REPL on stdin if there isn't one. With `--json`, the program is parsed
+/// and printed as JSON, one line per top-level form, instead of being run.
+/// With `--stats`, it's parsed and described by its size and shape.
+fn main() {
+    let mut json = false;
+    let mut stats = false;
+    let mut path = None;
 
-    println!("AST: {:#?}", ast);
-    println!("AST prettyprinted: {}", ast);
+    for arg in env::args_os().skip(1) {
+        if arg == "--stats" {
+            stats = true;
+        } else if arg == "--json" {
+            if !cfg!(feature = "json") {
+                eprintln!("--json needs lisp-rs to be built with the json feature");
+                process::exit(2);
+            }
+
+            json = true;
+        } else if path.is_none() {
+            path = Some(arg);
+        } else {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+
+    if json && stats {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let options = ParseOptions::new();
+    let ok = if json {
+        read(path.as_deref()).is_some_and(|code| print_json(&code, &options))
+    } else if stats {
+        read(path.as_deref()).is_some_and(|code| print_stats(&code, &options))
+    } else if path.is_some() {
+        read(path.as_deref()).is_some_and(|code| run(&code, &options, &mut Env::new()))
+    } else {
+        match repl(&options, &mut Env::new()) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Could not read from stdin: {}", e);
+                false
+            }
+        }
+    };
+
+    if !ok {
+        process::exit(1);
+    }
+}
+
+/// Reads the whole of the file at `path`, or of stdin if there's no path,
+/// reporting any error.
+fn read(path: Option<&OsStr>) -> Option<String> {
+    let result = match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut code = String::new();
+            io::stdin().read_to_string(&mut code).map(|_| code)
+        }
+    };
+
+    result
+        .map_err(|e| match path {
+            Some(path) => eprintln!("Could not read {}: {}", path.to_string_lossy(), e),
+            None => eprintln!("Could not read from stdin: {}", e),
+        })
+        .ok()
+}
+
+#[cfg(feature = "json")]
+fn print_json(code: &str, options: &ParseOptions) -> bool {
+    match parse_program_with(code, options) {
+        Ok(forms) => {
+            for form in forms {
+                println!("{}", form.to_json());
+            }
+
+            true
+        }
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json(_code: &str, _options: &ParseOptions) -> bool {
+    unreachable!("--json is rejected without the json feature")
+}
+
+fn print_stats(code: &str, options: &ParseOptions) -> bool {
+    match parse_program_with(code, options) {
+        Ok(forms) => {
+            print!("{}", Stats::of(&forms));
+            true
+        }
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            false
+        }
+    }
+}
+
+/// The size and shape of a program, as printed by `--stats`.
+#[derive(Debug, Default, PartialEq)]
+struct Stats {
+    forms: usize,
+    nodes: usize,
+    depth: usize,
+    /// How many atoms of each kind there are, by `Atom::kind`.
+    atoms: BTreeMap<&'static str, usize>,
+}
+
+impl Stats {
+    fn of(forms: &[Node]) -> Self {
+        let mut stats = Self {
+            forms: forms.len(),
+            ..Self::default()
+        };
+
+        for form in forms {
+            stats.nodes += form.node_count();
+            stats.depth = stats.depth.max(form.depth());
+            form.walk(&mut stats);
+        }
+
+        stats
+    }
+}
+
+impl Visitor for Stats {
+    fn visit_atom(&mut self, atom: &Atom) {
+        *self.atoms.entry(atom.kind()).or_default() += 1;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Top-level forms: {}", self.forms)?;
+        writeln!(f, "Nodes: {}", self.nodes)?;
+        writeln!(f, "Maximum depth: {}", self.depth)?;
+        writeln!(f, "Atoms:")?;
+
+        for (kind, count) in &self.atoms {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn repl(options: &ParseOptions, env: &mut Env) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut input = String::new();
+
+    loop {
+        prompt(if input.is_empty() { "> " } else { ". " })?;
+
+        match lines.next() {
+            Some(line) => {
+                input.push_str(&line?);
+                input.push('\n');
+            }
+            None => {
+                println!();
+                break;
+            }
+        }
+
+        if is_complete_with(&input, options) {
+            run(&input, options, env);
+            input.clear();
+        }
+    }
+
+    // Whatever was left unfinished at the end of the input is still worth
+    // reporting the error in.
+    run(&input, options, env);
 
     Ok(())
 }
+
+fn prompt(prompt: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(prompt.as_bytes())?;
+    stdout.flush()
+}
+
+/// Evaluates every form in `code`, printing each result or the first error.
+/// Returns whether there was no error.
+fn run(code: &str, options: &ParseOptions, env: &mut Env) -> bool {
+    let forms = match parse_program_with(code, options) {
+        Ok(forms) => forms,
+        Err(e) => {
+            eprintln!("Syntax error: {}", e);
+            return false;
+        }
+    };
+
+    for form in forms {
+        match eval(&form, env) {
+            Ok(value) => println!("{}", value),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use lisp_rs::parse_program;
+
+    #[test]
+    fn stats() {
+        let forms = parse_program("(define x 1) (f (g \"s\" 2.5)) y").unwrap();
+        let stats = Stats::of(&forms);
+
+        assert_eq!(stats.forms, 3);
+        assert_eq!(stats.nodes, 11);
+        assert_eq!(stats.depth, 2);
+        assert_eq!(
+            stats.to_string(),
+            "Top-level forms: 3\n\
+             Nodes: 11\n\
+             Maximum depth: 2\n\
+             Atoms:\n  \
+             Float: 1\n  \
+             Int: 1\n  \
+             Str: 1\n  \
+             Symbol: 5\n"
+        );
+        assert_eq!(Stats::of(&[]).to_string().lines().count(), 4);
+    }
+}
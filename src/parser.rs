@@ -1,328 +1,4796 @@
-use std::{error::Error, fmt};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use core::cmp;
+use core::{
+    fmt::{self, Write as _},
+    hash::{Hash, Hasher},
+    iter::{self, Peekable},
+    mem,
+    num::IntErrorKind,
+    ops::Range,
+    slice,
+    str::Chars,
+};
+#[cfg(feature = "std")]
+use std::{error::Error, io::Read};
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::symbol::Symbol;
+
+/// How deeply lists and reader macros may nest before parsing gives up with
+/// `SexprSyntaxError::DepthLimitExceeded`. Parsing itself doesn't recurse,
+/// but dropping, comparing and displaying the resulting `Node` all do, so
+/// this is kept low for the reasons given at `DEFAULT_EVAL_DEPTH_LIMIT`. The
+/// pairs of a dotted list nest without any parens, but those are followed in
+/// a loop, so only parens count towards the depth.
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+/// The type of number `Atom::Float` holds. That's an `f64`, unless the
+/// `small-float` feature is on, in which case it's an `f32`. That halves the
+/// size of a float, which can matter on embedded and wasm targets, but
+/// leaves only about 7 significant digits rather than 16, and a much
+/// smaller range, so more literals round or overflow to infinity.
+#[cfg(not(feature = "small-float"))]
+pub type Float = f64;
+#[cfg(feature = "small-float")]
+pub type Float = f32;
 
 pub fn parse_sexpr(code: &str) -> Result<Node, SexprSyntaxError> {
-    parse_tokens(&tokenize(code))
+    parse_sexpr_with(code, &ParseOptions::new())
 }
 
-fn parse_tokens(tokens: &[String]) -> Result<Node, SexprSyntaxError> {
-    if let Some(first) = tokens.first() {
-        if first != "(" {
-            return if tokens.len() == 1 && first != ")" {
-                Ok(Node::Atom(parse_atom(first)))
-            } else {
-                Err(SexprSyntaxError::UnmatchedParen)
+/// Like `parse_sexpr`, but with a nesting limit other than
+/// `DEFAULT_MAX_DEPTH`.
+pub fn parse_sexpr_with_max_depth(code: &str, max_depth: usize) -> Result<Node, SexprSyntaxError> {
+    parse_sexpr_with(code, &ParseOptions::new().max_depth(max_depth))
+}
+
+/// Like `parse_sexpr`, but following `options` rather than the defaults.
+pub fn parse_sexpr_with(code: &str, options: &ParseOptions) -> Result<Node, SexprSyntaxError> {
+    Parser::new(code, options.clone()).parse()
+}
+
+/// Like `parse_sexpr`, but reading the code from `reader`, such as an open
+/// file. All of it is read before parsing starts. The error is either the
+/// `io::Error` from reading, including for input that isn't UTF-8, or the
+/// `SexprSyntaxError` from parsing.
+#[cfg(feature = "std")]
+pub fn parse_from_reader<R: Read>(mut reader: R) -> Result<Node, Box<dyn Error>> {
+    let mut code = String::new();
+
+    reader.read_to_string(&mut code)?;
+
+    Ok(parse_sexpr(&code)?)
+}
+
+/// Settings for the parser that most code has no need to change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    max_depth: usize,
+    allow_reader_macros: bool,
+    bracket_vectors: bool,
+    distinct_nil: bool,
+    keep_radix: bool,
+    fold_case: bool,
+    strict_symbols: bool,
+    symbol_chars: Cow<'static, str>,
+}
+
+impl ParseOptions {
+    /// The punctuation `ParseOptions::new` allows in strict symbols.
+    pub const DEFAULT_SYMBOL_CHARS: &'static str = "+-*/<>=!?";
+
+    /// Creates the options `parse_sexpr` uses: a nesting limit of
+    /// `DEFAULT_MAX_DEPTH`, reader macros allowed, `[...]` read as a list,
+    /// `nil` read as a symbol,
+    /// and any symbol accepted, though strict symbols would only allow
+    /// alphanumerics and `DEFAULT_SYMBOL_CHARS`.
+    pub fn new() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            allow_reader_macros: true,
+            bracket_vectors: false,
+            distinct_nil: false,
+            keep_radix: false,
+            fold_case: false,
+            strict_symbols: false,
+            symbol_chars: Cow::Borrowed(Self::DEFAULT_SYMBOL_CHARS),
+        }
+    }
+
+    /// Sets how deeply lists and reader macros may nest before parsing gives
+    /// up with `SexprSyntaxError::DepthLimitExceeded`.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets whether `'`, `` ` ``, `,` and `,@` may be used as shorthand for
+    /// `quote` and friends. If not, any of them is a
+    /// `SexprSyntaxError::DisabledReaderMacro` error.
+    pub fn allow_reader_macros(mut self, allow: bool) -> Self {
+        self.allow_reader_macros = allow;
+        self
+    }
+
+    /// Sets whether `[...]` is read as a vector, as in Clojure, rather than
+    /// as another way of writing a list. Either way it has to be closed with
+    /// a `]`, and a `(` with a `)`.
+    pub fn bracket_vectors(mut self, vectors: bool) -> Self {
+        self.bracket_vectors = vectors;
+        self
+    }
+
+    /// Sets whether the bare symbol `nil` is read as `Atom::Nil`. Either way
+    /// `()` is an empty list, and a pipe-quoted `|nil|` is still a symbol.
+    pub fn distinct_nil(mut self, distinct: bool) -> Self {
+        self.distinct_nil = distinct;
+        self
+    }
+
+    /// Sets whether integers written with a `#x`, `#o` or `#b` prefix are
+    /// read as `Atom::RadixInt`, which displays in the same radix, rather
+    /// than as a plain `Atom::Int`. Integers too large for an `i64` are
+    /// `Atom::BigInt`s either way.
+    pub fn keep_radix(mut self, keep: bool) -> Self {
+        self.keep_radix = keep;
+        self
+    }
+
+    /// Sets whether bare symbols and keywords are folded to lower case as
+    /// they're read, so that `CAR` and `car` are the same symbol, as in
+    /// code written for a case-insensitive Lisp. Booleans and the names of
+    /// characters are folded too, so `#T` is `#t` and `#\SPACE` a space,
+    /// but `#\A` is still an upper case `A`. A pipe-quoted symbol like
+    /// `|CAR|` keeps its case either way.
+    pub fn fold_case(mut self, fold: bool) -> Self {
+        self.fold_case = fold;
+        self
+    }
+
+    /// Sets whether a symbol containing anything other than alphanumerics
+    /// and the `symbol_chars` is an `InvalidSymbol` error. Otherwise any
+    /// token that isn't some other kind of atom is read as a symbol, even
+    /// one like `#foo` that looks like a reader macro gone wrong, or one
+    /// like `a$b` with punctuation few Lisps allow.
+    pub fn strict_symbols(mut self, strict: bool) -> Self {
+        self.strict_symbols = strict;
+        self
+    }
+
+    /// Sets the characters besides alphanumerics that strict symbols may
+    /// contain.
+    pub fn symbol_chars(mut self, chars: &str) -> Self {
+        self.symbol_chars = Cow::Owned(chars.to_owned());
+        self
+    }
+
+    fn fold<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.fold_case && name.chars().any(char::is_uppercase) {
+            Cow::Owned(name.to_lowercase())
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    fn allows_symbol(&self, name: &str) -> bool {
+        !self.strict_symbols
+            || name
+                .chars()
+                .all(|c| c.is_alphanumeric() || self.symbol_chars.contains(c))
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a sequence of top-level S-expressions, such as the contents of a
+/// source file. Unlike `parse_sexpr`, input with no expressions in it is not
+/// an error.
+pub fn parse_program(code: &str) -> Result<Vec<Node>, SexprSyntaxError> {
+    parse_program_with(code, &ParseOptions::new())
+}
+
+/// Like `parse_program`, but following `options` rather than the defaults.
+pub fn parse_program_with(
+    code: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Node>, SexprSyntaxError> {
+    Parser::new(code, options.clone()).collect()
+}
+
+/// Parses the first complete expression in `code`, giving it along with the
+/// rest of the input after it, so that a stream of forms can be read one at
+/// a time by calling this again on what's left. Like `parse_sexpr`, input
+/// with no expression in it is `SexprSyntaxError::Empty`. The positions in
+/// errors are relative to the `code` passed in, not to any input the
+/// remainder was taken from.
+pub fn parse_one(code: &str) -> Result<(Node, &str), SexprSyntaxError> {
+    parse_one_with(code, &ParseOptions::new())
+}
+
+/// Like `parse_one`, but following `options` rather than the defaults.
+pub fn parse_one_with<'a>(
+    code: &'a str,
+    options: &ParseOptions,
+) -> Result<(Node, &'a str), SexprSyntaxError> {
+    let mut parser = Parser::new(code, options.clone());
+    let (node, spans) = parser.next_spanned().ok_or(SexprSyntaxError::Empty)??;
+
+    Ok((node, &code[spans.span.end..]))
+}
+
+/// Like `parse_sexpr`, but also gives the spans of the source that the node
+/// and everything inside it were parsed from.
+pub fn parse_sexpr_spanned(code: &str) -> Result<(Node, Spans), SexprSyntaxError> {
+    parse_sexpr_spanned_with(code, &ParseOptions::new())
+}
+
+/// Like `parse_sexpr_spanned`, but following `options` rather than the
+/// defaults.
+pub fn parse_sexpr_spanned_with(
+    code: &str,
+    options: &ParseOptions,
+) -> Result<(Node, Spans), SexprSyntaxError> {
+    Parser::new(code, options.clone())
+        .tracking_spans()
+        .parse_spanned()
+}
+
+/// Like `parse_program`, but also gives the spans of the source that each
+/// top-level node and everything inside it were parsed from.
+pub fn parse_program_spanned(code: &str) -> Result<Vec<(Node, Spans)>, SexprSyntaxError> {
+    parse_program_spanned_with(code, &ParseOptions::new())
+}
+
+/// Like `parse_program_spanned`, but following `options` rather than the
+/// defaults.
+pub fn parse_program_spanned_with(
+    code: &str,
+    options: &ParseOptions,
+) -> Result<Vec<(Node, Spans)>, SexprSyntaxError> {
+    let mut parser = Parser::new(code, options.clone()).tracking_spans();
+
+    iter::from_fn(|| parser.next_spanned()).collect()
+}
+
+/// Like `parse_program`, but carrying on past errors rather than stopping at
+/// the first, giving every form that parsed along with every error found.
+/// After an error, parsing starts again after the end of the top-level form
+/// it was in, found by counting brackets from where the form began. An error
+/// in lexing a token, such as a string that's never closed, ends parsing
+/// there, since there's no telling where the token was meant to end.
+pub fn parse_program_recover(code: &str) -> (Vec<Node>, Vec<SexprSyntaxError>) {
+    parse_program_recover_with(code, &ParseOptions::new())
+}
+
+/// Like `parse_program_recover`, but following `options` rather than the
+/// defaults.
+pub fn parse_program_recover_with(
+    code: &str,
+    options: &ParseOptions,
+) -> (Vec<Node>, Vec<SexprSyntaxError>) {
+    let mut parser = Parser::new(code, options.clone());
+    let mut forms = vec![];
+    let mut errors = vec![];
+
+    loop {
+        let start = match parser.tokens.peek() {
+            Some(Ok(token)) => token.span.start,
+            Some(Err(_)) => {
+                errors.extend(parser.next_token().err());
+                break;
+            }
+            None => break,
+        };
+
+        match parser.parse_datum() {
+            Ok((node, _)) => forms.push(node),
+            Err(e) => {
+                errors.push(e);
+
+                if !parser.skip_form(start) {
+                    break;
+                }
+            }
+        }
+    }
+
+    (forms, errors)
+}
+
+/// Whether `code` is everything it needs to be to parse, rather than the
+/// start of something that more input could finish, such as a list that
+/// hasn't been closed yet. Malformed code that no amount of further input
+/// could fix, like an extra `)`, counts as complete, so that a REPL reading
+/// lines until this holds goes on to report the error.
+pub fn is_complete(code: &str) -> bool {
+    is_complete_with(code, &ParseOptions::new())
+}
+
+/// Like `is_complete`, but following `options` rather than the defaults, so
+/// that code which only parses under those options counts as complete.
+pub fn is_complete_with(code: &str, options: &ParseOptions) -> bool {
+    !matches!(
+        parse_program_with(code, options),
+        Err(SexprSyntaxError::UnexpectedEof(_))
+            | Err(SexprSyntaxError::UnterminatedString(_))
+            | Err(SexprSyntaxError::UnterminatedSymbol(_))
+            | Err(SexprSyntaxError::UnterminatedComment(_))
+    )
+}
+
+/// Returns an iterator over the tokens in `code`. Whitespace and comments
+/// separate tokens but aren't tokens themselves, so they're skipped. The
+/// exception is the `#;` of a datum comment, which is left for the parser.
+pub fn tokenize(code: &str) -> Tokenizer<'_> {
+    Tokenizer::new(code)
+}
+
+/// Pulls tokens out of a piece of source code as it needs them, keeping the
+/// code itself around so that token spans can be turned into positions when
+/// reporting errors. As an iterator, it parses one top-level form at a time.
+struct Parser<'a> {
+    code: &'a str,
+    tokens: Peekable<Tokenizer<'a>>,
+    options: ParseOptions,
+    /// Whether to record the spans of the children of lists, which costs an
+    /// allocation per list that most callers have no use for.
+    spans: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(code: &'a str, options: ParseOptions) -> Self {
+        Self {
+            code,
+            tokens: Tokenizer::new(code).peekable(),
+            options,
+            spans: false,
+        }
+    }
+
+    fn tracking_spans(mut self) -> Self {
+        self.spans = true;
+        self
+    }
+
+    fn parse(&mut self) -> Result<Node, SexprSyntaxError> {
+        self.parse_spanned().map(|(node, _)| node)
+    }
+
+    fn parse_spanned(&mut self) -> Result<(Node, Spans), SexprSyntaxError> {
+        if self.tokens.peek().is_none() {
+            return Err(SexprSyntaxError::Empty);
+        }
+
+        let node = self.parse_datum()?;
+        let trailing = match self.next_token()? {
+            Some(trailing) => trailing,
+            None => return Ok(node),
+        };
+        let position = self.position_of(&trailing);
+
+        Err(match trailing.kind {
+            TokenKind::RParen | TokenKind::RBracket => {
+                SexprSyntaxError::UnexpectedCloseParen(position)
+            }
+            // A commented-out datum leaves nothing behind, so it only counts
+            // as trailing if something else comes after it.
+            TokenKind::DatumComment => {
+                let stack = vec![Frame::DatumComment {
+                    start: trailing.span.start,
+                }];
+
+                match self.parse_datum_from(stack) {
+                    Err(SexprSyntaxError::Empty) => return Ok(node),
+                    Err(e) => e,
+                    Ok((_, spans)) => {
+                        SexprSyntaxError::TrailingTokens(self.locate(spans.span.start))
+                    }
+                }
+            }
+            _ => SexprSyntaxError::TrailingTokens(position),
+        })
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, SexprSyntaxError> {
+        self.tokens.next().transpose()
+    }
+
+    fn position_of(&self, token: &Token) -> Position {
+        self.locate(token.span.start)
+    }
+
+    fn locate(&self, offset: usize) -> Position {
+        Position::locate(self.code, offset)
+    }
+
+    /// Moves past the top-level form starting at `start`, by lexing it again
+    /// from the beginning until its brackets balance. Gives `false` if one
+    /// of its tokens can't be lexed, since then there's no finding its end.
+    fn skip_form(&mut self, start: usize) -> bool {
+        self.tokens = Tokenizer::starting_at(self.code, start).peekable();
+
+        let mut depth = 0usize;
+
+        loop {
+            let token = match self.next_token() {
+                Ok(Some(token)) => token,
+                Ok(None) => return true,
+                Err(_) => return false,
+            };
+
+            match token.kind {
+                TokenKind::LParen | TokenKind::LVector | TokenKind::LBracket => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket => depth = depth.saturating_sub(1),
+                ref kind if kind.reader_macro().is_some() => continue,
+                _ => {}
+            }
+
+            if depth == 0 {
+                return true;
+            }
+        }
+    }
+
+    /// Parses one top-level form, if there are any left.
+    fn next_spanned(&mut self) -> Option<Result<(Node, Spans), SexprSyntaxError>> {
+        self.tokens.peek()?;
+
+        // Only datum comments are left if there's nothing to parse.
+        match self.parse_datum() {
+            Err(SexprSyntaxError::Empty) => None,
+            result => Some(result),
+        }
+    }
+
+    fn parse_datum(&mut self) -> Result<(Node, Spans), SexprSyntaxError> {
+        self.parse_datum_from(vec![])
+    }
+
+    /// Parses the next complete datum out of `tokens`, inside the lists and
+    /// reader macros already on `stack`. Rather than recursing into nested
+    /// lists, this keeps an explicit stack of those still waiting for their
+    /// contents, so that deep nesting can't overflow the call stack.
+    fn parse_datum_from(
+        &mut self,
+        mut stack: Vec<Frame>,
+    ) -> Result<(Node, Spans), SexprSyntaxError> {
+        loop {
+            let token = match self.next_token()? {
+                Some(token) => token,
+                None => return Err(self.unclosed_error(&stack)),
+            };
+
+            let mut datum = match &token.kind {
+                TokenKind::RParen | TokenKind::RBracket => match stack.pop() {
+                    Some(Frame::List { start, bracket, .. })
+                        if bracket != (token.kind == TokenKind::RBracket) =>
+                    {
+                        return Err(SexprSyntaxError::MismatchedBracket {
+                            open: self.locate(start),
+                            close: self.position_of(&token),
+                        })
+                    }
+                    Some(Frame::List {
+                        start,
+                        elements,
+                        children,
+                        dot,
+                        vector: false,
+                        ..
+                    }) => self.finish_list(start..token.span.end, elements, children, dot)?,
+                    Some(Frame::List {
+                        start,
+                        elements,
+                        children,
+                        vector: true,
+                        ..
+                    }) => (
+                        Node::Vector(elements),
+                        Spans {
+                            span: start..token.span.end,
+                            children,
+                        },
+                    ),
+                    Some(Frame::ReaderMacro { span, .. }) => {
+                        return Err(SexprSyntaxError::DanglingReaderMacro(
+                            self.locate(span.start),
+                        ))
+                    }
+                    Some(Frame::DatumComment { start }) => {
+                        return Err(SexprSyntaxError::DanglingReaderMacro(self.locate(start)))
+                    }
+                    None => {
+                        return Err(SexprSyntaxError::UnexpectedCloseParen(
+                            self.position_of(&token),
+                        ))
+                    }
+                },
+                TokenKind::Dot => {
+                    match stack.last_mut() {
+                        Some(Frame::List {
+                            elements,
+                            dot: dot @ None,
+                            vector: false,
+                            ..
+                        }) if !elements.is_empty() => {
+                            *dot = Some(DottedTail {
+                                start: token.span.start,
+                                tail: None,
+                            });
+                        }
+                        Some(Frame::List { dot: Some(dot), .. }) => {
+                            return Err(SexprSyntaxError::InvalidDottedPair(self.locate(dot.start)))
+                        }
+                        _ => {
+                            return Err(SexprSyntaxError::InvalidDottedPair(
+                                self.position_of(&token),
+                            ))
+                        }
+                    }
+
+                    continue;
+                }
+                TokenKind::Str(s) => (
+                    Node::Atom(Atom::Str(s.to_string())),
+                    Spans::leaf(token.span.clone()),
+                ),
+                TokenKind::Atom(atom) => (
+                    Node::Atom(self.parse_atom(atom, &token)?),
+                    Spans::leaf(token.span.clone()),
+                ),
+                TokenKind::Symbol(name) => (
+                    Node::Atom(Atom::Symbol(Symbol::new(name))),
+                    Spans::leaf(token.span.clone()),
+                ),
+                kind => {
+                    if stack.len() >= self.options.max_depth {
+                        return Err(SexprSyntaxError::DepthLimitExceeded(
+                            self.position_of(&token),
+                        ));
+                    }
+
+                    let start = token.span.start;
+
+                    stack.push(match kind.reader_macro() {
+                        Some(_) if !self.options.allow_reader_macros => {
+                            return Err(SexprSyntaxError::DisabledReaderMacro(
+                                self.position_of(&token),
+                            ))
+                        }
+                        Some(name) => Frame::ReaderMacro {
+                            name,
+                            span: token.span.clone(),
+                        },
+                        None if *kind == TokenKind::DatumComment => Frame::DatumComment { start },
+                        None => Frame::List {
+                            start,
+                            elements: vec![],
+                            children: vec![],
+                            dot: None,
+                            vector: *kind == TokenKind::LVector
+                                || (*kind == TokenKind::LBracket && self.options.bracket_vectors),
+                            bracket: *kind == TokenKind::LBracket,
+                        },
+                    });
+
+                    continue;
+                }
             };
+
+            // Hand the finished datum to whatever encloses it, completing
+            // any reader macros that were only waiting on it along the way.
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(datum),
+                    Some(Frame::DatumComment { .. }) => {
+                        stack.pop();
+                        break;
+                    }
+                    Some(Frame::ReaderMacro { name, span }) => {
+                        let (node, spans) = datum;
+                        let whole = span.start..spans.span.end;
+                        let children = if self.spans {
+                            vec![Spans::leaf(span.clone()), spans]
+                        } else {
+                            vec![]
+                        };
+
+                        datum = (
+                            Node::List(vec![Node::Atom(Atom::Symbol(Symbol::new(name))), node]),
+                            Spans {
+                                span: whole,
+                                children,
+                            },
+                        );
+                        stack.pop();
+                    }
+                    Some(Frame::List {
+                        elements,
+                        children,
+                        dot,
+                        ..
+                    }) => {
+                        match dot {
+                            None => {
+                                let (node, spans) = datum;
+
+                                elements.push(node);
+
+                                if self.spans {
+                                    children.push(spans);
+                                }
+                            }
+                            Some(DottedTail {
+                                tail: tail @ None, ..
+                            }) => *tail = Some(datum),
+                            Some(DottedTail { start, .. }) => {
+                                return Err(SexprSyntaxError::InvalidDottedPair(
+                                    self.locate(*start),
+                                ))
+                            }
+                        }
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_atom(&self, atom: &str, token: &Token) -> Result<Atom, SexprSyntaxError> {
+        Ok(
+            if let Some(boolean) = parse_bool(&self.options.fold(atom)) {
+                Atom::Bool(boolean)
+            } else if let Some(c) = parse_char(atom, &self.options) {
+                Atom::Char(c.ok_or_else(|| {
+                    SexprSyntaxError::InvalidChar(atom.to_owned(), self.position_of(token))
+                })?)
+            } else if let Some(number) = parse_radix_int(atom, self.options.keep_radix)
+                .or_else(|| parse_rational(atom))
+                .or_else(|| parse_number(atom))
+            {
+                number.map_err(|e| {
+                    let (atom, position) = (atom.to_owned(), self.position_of(token));
+
+                    match e {
+                        NumberError::Invalid => SexprSyntaxError::InvalidNumber(atom, position),
+                        NumberError::Overflow => SexprSyntaxError::IntegerOverflow(atom, position),
+                    }
+                })?
+            } else if let Some(keyword) = parse_keyword(atom) {
+                Atom::Keyword(self.options.fold(keyword).into_owned())
+            } else if self.options.distinct_nil && self.options.fold(atom) == "nil" {
+                Atom::Nil
+            } else if self.options.allows_symbol(atom) {
+                Atom::Symbol(Symbol::new(&self.options.fold(atom)))
+            } else {
+                return Err(SexprSyntaxError::InvalidSymbol(
+                    atom.to_owned(),
+                    self.position_of(token),
+                ));
+            },
+        )
+    }
+
+    /// Builds a list out of its elements once its closing paren is reached.
+    /// Lists like `(a b . c)` become chains of pairs ending in their tail.
+    fn finish_list(
+        &self,
+        span: Range<usize>,
+        elements: Vec<Node>,
+        mut children: Vec<Spans>,
+        dot: Option<DottedTail>,
+    ) -> Result<(Node, Spans), SexprSyntaxError> {
+        let node = match dot {
+            None => Node::List(elements),
+            Some(DottedTail {
+                tail: Some((tail, tail_spans)),
+                ..
+            }) => {
+                if self.spans {
+                    children.push(tail_spans);
+                }
+
+                elements
+                    .into_iter()
+                    .rev()
+                    .fold(tail, |cdr, car| Node::Pair(Box::new(car), Box::new(cdr)))
+            }
+            Some(DottedTail { start, tail: None }) => {
+                return Err(SexprSyntaxError::InvalidDottedPair(self.locate(start)))
+            }
+        };
+
+        Ok((node, Spans { span, children }))
+    }
+
+    /// Picks the error to report when the input runs out while there are
+    /// still unfinished frames on the stack. An unclosed list is reported
+    /// against its outermost opening paren, since that's the one the rest of
+    /// the input failed to close.
+    fn unclosed_error(&self, stack: &[Frame]) -> SexprSyntaxError {
+        let unclosed = stack
+            .iter()
+            .find(|frame| matches!(frame, Frame::List { .. }))
+            .or_else(|| stack.last());
+
+        match unclosed {
+            Some(Frame::List { start, .. }) => SexprSyntaxError::UnexpectedEof(self.locate(*start)),
+            Some(Frame::ReaderMacro { span, .. }) => {
+                SexprSyntaxError::UnexpectedEof(self.locate(span.start))
+            }
+            Some(Frame::DatumComment { start }) => {
+                SexprSyntaxError::UnexpectedEof(self.locate(*start))
+            }
+            None => SexprSyntaxError::Empty,
+        }
+    }
+}
+
+impl Iterator for Parser<'_> {
+    type Item = Result<Node, SexprSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_spanned()?.map(|(node, _)| node))
+    }
+}
+
+/// A list, reader macro or datum comment that is still being parsed.
+enum Frame {
+    List {
+        /// The byte offset of the opening paren.
+        start: usize,
+        elements: Vec<Node>,
+        /// The spans of `elements`, if the parser is tracking them.
+        children: Vec<Spans>,
+        dot: Option<DottedTail>,
+        /// Whether this will be a vector, because it was opened by `#(` or by
+        /// a `[` read as one.
+        vector: bool,
+        /// Whether this was opened by `[`, and so needs closing with `]`.
+        bracket: bool,
+    },
+    ReaderMacro {
+        name: &'static str,
+        /// The span of the reader macro's own token.
+        span: Range<usize>,
+    },
+    /// A `#;`, whose datum is thrown away once it's read.
+    DatumComment { start: usize },
+}
+
+/// The `.` of a dotted pair, along with the datum after it once it's read.
+struct DottedTail {
+    start: usize,
+    tail: Option<(Node, Spans)>,
+}
+
+/// The byte ranges in the source that a node and everything inside it were
+/// parsed from. The children line up with `Node::children`, so the tail of a
+/// dotted list comes last, and a reader macro like `'x` has a child for the
+/// `'` standing in for its `quote`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Spans {
+    pub span: Range<usize>,
+    pub children: Vec<Spans>,
+}
+
+impl Spans {
+    fn leaf(span: Range<usize>) -> Self {
+        Self {
+            span,
+            children: vec![],
         }
+    }
+}
+
+/// Why a token that had to be a number couldn't be parsed as one.
+enum NumberError {
+    Invalid,
+    /// An integer, or part of a rational, that's too large for an `i64`.
+    Overflow,
+}
+
+/// Parses integers written with a `#x`, `#o` or `#b` radix prefix. Once a
+/// token has one of these prefixes it must be a valid integer in that radix,
+/// so an error means it should be reported rather than treated as a symbol.
+/// With `keep_radix`, the integer remembers which prefix it had.
+fn parse_radix_int(atom: &str, keep_radix: bool) -> Option<Result<Atom, NumberError>> {
+    let radix = match atom.get(..2) {
+        Some("#x") | Some("#X") => Radix::Hex,
+        Some("#o") | Some("#O") => Radix::Octal,
+        Some("#b") | Some("#B") => Radix::Binary,
+        _ => return None,
+    };
+
+    Some(
+        parse_int(&atom[2..], radix.base()).map(|number| match number {
+            Atom::Int(value) if keep_radix => Atom::RadixInt(value, radix),
+            number => number,
+        }),
+    )
+}
+
+/// Parses numeric literals, which may carry a single leading `+` or `-`.
+/// A token only counts as a number if a digit (or a `.` and then a digit)
+/// follows the sign, so the operators `+` and `-` on their own, as well as
+/// words like `inf` and `nan` that `f64::from_str` would otherwise accept,
+/// stay symbols. Infinities and `NaN` are instead written the way R7RS
+/// Scheme writes them, as `+inf.0`, `-inf.0` and `+nan.0` (or `-nan.0`). An
+/// integer that overflows is an error rather than being read as a float,
+/// which would quietly lose its exactness.
+fn parse_number(atom: &str) -> Option<Result<Atom, NumberError>> {
+    match atom {
+        "+inf.0" => return Some(Ok(Atom::Float(Float::INFINITY))),
+        "-inf.0" => return Some(Ok(Atom::Float(Float::NEG_INFINITY))),
+        "+nan.0" | "-nan.0" => return Some(Ok(Atom::Float(Float::NAN))),
+        _ => {}
+    }
+
+    let unsigned = atom.strip_prefix(|c| c == '+' || c == '-').unwrap_or(atom);
+    let mut chars = unsigned.chars();
+
+    let starts_numeric = match chars.next() {
+        Some('.') => matches!(chars.next(), Some(c) if c.is_ascii_digit()),
+        Some(c) => c.is_ascii_digit(),
+        None => false,
+    };
+
+    if !starts_numeric {
+        return None;
+    }
+
+    match parse_int(atom, 10) {
+        Err(NumberError::Invalid) => atom.parse().ok().map(|float| Ok(Atom::Float(float))),
+        integer => Some(integer),
+    }
+}
+
+/// Parses exact rationals such as `3/4` or `-6/8`, reducing them to lowest
+/// terms. One that reduces to a whole number, like `4/2`, is an `Int`. An
+/// error means the token looks like a rational but can't be one, because
+/// its denominator is zero or a part of it is too large for an `i64`.
+fn parse_rational(atom: &str) -> Option<Result<Atom, NumberError>> {
+    let (num, den) = atom.split_once('/')?;
+    let unsigned = num.strip_prefix(|c| c == '+' || c == '-').unwrap_or(num);
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    if !is_digits(unsigned) || !is_digits(den) {
+        return None;
+    }
+
+    // Both parts are known to be digits, so the only way parsing them can
+    // fail is by overflowing.
+    let (num, den) = match (num.parse::<i64>(), den.parse::<i64>()) {
+        (Ok(_), Ok(0)) => return Some(Err(NumberError::Invalid)),
+        (Ok(num), Ok(den)) => (num, den),
+        _ => return Some(Err(NumberError::Overflow)),
+    };
+
+    let divisor = gcd(num.unsigned_abs(), den.unsigned_abs()) as i64;
+    let (num, den) = (num / divisor, den / divisor);
+
+    Some(Ok(if den == 1 {
+        Atom::Int(num)
     } else {
-        return Err(SexprSyntaxError::Empty);
+        Atom::Rational { num, den }
+    }))
+}
+
+pub(crate) fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+
+    a
+}
+
+/// Parses an integer in the given radix. With the `bigint` feature, values
+/// too large for an `i64` are promoted to `Atom::BigInt` instead of failing.
+fn parse_int(digits: &str, radix: u32) -> Result<Atom, NumberError> {
+    // `from_str_radix` reports an overflow as soon as the value gets too
+    // large, before it's looked at the rest of the digits, so something
+    // like `99999999999999999999.5` needs ruling out first.
+    let unsigned = digits
+        .strip_prefix(|c| c == '+' || c == '-')
+        .unwrap_or(digits);
+
+    if !unsigned.chars().all(|c| c.is_digit(radix)) {
+        return Err(NumberError::Invalid);
+    }
+
+    match i64::from_str_radix(digits, radix) {
+        Ok(integer) => Ok(Atom::Int(integer)),
+        Err(e) => match e.kind() {
+            #[cfg(feature = "bigint")]
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                BigInt::parse_bytes(digits.as_bytes(), radix)
+                    .map(Atom::BigInt)
+                    .ok_or(NumberError::Invalid)
+            }
+            #[cfg(not(feature = "bigint"))]
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Err(NumberError::Overflow),
+            _ => Err(NumberError::Invalid),
+        },
+    }
+}
+
+/// Recognizes keywords such as `:name`, returning the name without its
+/// colon. A `:` on its own has no name, so it's left to be a symbol.
+fn parse_keyword(atom: &str) -> Option<&str> {
+    atom.strip_prefix(':').filter(|name| !name.is_empty())
+}
+
+/// Parses character literals such as `#\a`, or `#\space`, `#\newline` and
+/// `#\tab` for the characters that can't be written directly. As with
+/// `parse_radix_int`, the inner `None` means the `#\` prefix was there but
+/// what follows it isn't a character. Names are folded like symbols, but a
+/// single character is always itself, so `#\A` stays an upper case `A`.
+fn parse_char(atom: &str, options: &ParseOptions) -> Option<Option<char>> {
+    let name = atom.strip_prefix("#\\")?;
+    let mut chars = name.chars();
+
+    Some(match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => match &*options.fold(name) {
+            "space" => Some(' '),
+            "newline" => Some('\n'),
+            "tab" => Some('\t'),
+            _ => None,
+        },
+    })
+}
+
+/// Recognizes boolean literals. The long forms `#true` and `#false` are
+/// accepted alongside `#t` and `#f` because R7RS Scheme defines them as exact
+/// synonyms, and nothing else could sensibly be meant by them. They always
+/// display in the short form.
+fn parse_bool(atom: &str) -> Option<bool> {
+    match atom {
+        "#t" | "#true" => Some(true),
+        "#f" | "#false" => Some(false),
+        _ => None,
+    }
+}
+
+/// A token borrowing from the source code it was read from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    /// The byte range in the source the token was read from, so that
+    /// `&code[token.span]` is the token's original text.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum TokenKind<'a> {
+    LParen,
+    /// The `#(` that opens a vector, which is closed by an ordinary `)`.
+    LVector,
+    /// The `[` that opens a list or vector, depending on
+    /// `ParseOptions::bracket_vectors`.
+    LBracket,
+    RBracket,
+    RParen,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    /// The `.` separating the tail of a dotted pair. Only a `.` with a
+    /// delimiter or whitespace on either side counts, so one that's part of
+    /// a longer token, as in `.5`, `a.b` or `...`, belongs to an atom.
+    Dot,
+    /// A string literal, with its escape sequences already decoded. This is
+    /// only an owned `String` when there were escapes to decode.
+    Str(Cow<'a, str>),
+    /// A symbol written between pipes, as in `|hello world|`, with escapes
+    /// decoded the same way as in a string. It's always a symbol, whatever
+    /// its name looks like, and `ParseOptions::strict_symbols` doesn't apply
+    /// to it.
+    Symbol(Cow<'a, str>),
+    /// Any other atom, such as a number or a symbol.
+    Atom(&'a str),
+    /// The `#;` that comments out the datum after it. Unlike other comments
+    /// it's always a token, since it takes the parser to know where that
+    /// datum ends.
+    DatumComment,
+    /// A `;` line comment or `#| |#` block comment, exactly as written. Only
+    /// a tokenizer made with `Tokenizer::keep_comments` yields these.
+    Comment(&'a str),
+}
+
+impl TokenKind<'_> {
+    /// Returns the symbol a reader macro token expands to, if it is one.
+    fn reader_macro(&self) -> Option<&'static str> {
+        match self {
+            Self::Quote => Some("quote"),
+            Self::Quasiquote => Some("quasiquote"),
+            Self::Unquote => Some("unquote"),
+            Self::UnquoteSplicing => Some("unquote-splicing"),
+            _ => None,
+        }
+    }
+}
+
+/// Lexes tokens out of source code lazily, one at a time, so that the parser
+/// never needs to hold more than the token it's currently looking at. Once
+/// it has yielded an error, it yields nothing more. Created by `tokenize`.
+pub struct Tokenizer<'a> {
+    chars: Scanner<'a>,
+    failed: bool,
+    comments: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(code: &'a str) -> Self {
+        Self {
+            chars: Scanner::new(code),
+            failed: false,
+            comments: false,
+        }
+    }
+
+    /// Starts lexing from the byte `offset` into `code`, which must be the
+    /// start of a token.
+    fn starting_at(code: &'a str, offset: usize) -> Self {
+        Self {
+            chars: Scanner::starting_at(code, offset),
+            failed: false,
+            comments: false,
+        }
+    }
+
+    /// Yields comments as `TokenKind::Comment` tokens rather than skipping
+    /// them, for tools such as formatters that need to put them back.
+    pub fn keep_comments(mut self) -> Self {
+        self.comments = true;
+        self
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, SexprSyntaxError> {
+        let chars = &mut self.chars;
+
+        while let Some(c) = chars.peek() {
+            let start = chars.offset;
+
+            let kind = match c {
+                '(' | ')' | '[' | ']' | '\'' | '`' => {
+                    chars.next();
+
+                    match c {
+                        '(' => TokenKind::LParen,
+                        ')' => TokenKind::RParen,
+                        '[' => TokenKind::LBracket,
+                        ']' => TokenKind::RBracket,
+                        '\'' => TokenKind::Quote,
+                        _ => TokenKind::Quasiquote,
+                    }
+                }
+                ',' => {
+                    chars.next();
+
+                    if chars.next_if_eq('@') {
+                        TokenKind::UnquoteSplicing
+                    } else {
+                        TokenKind::Unquote
+                    }
+                }
+                '"' => TokenKind::Str(read_quoted(
+                    chars,
+                    '"',
+                    SexprSyntaxError::UnterminatedString,
+                )?),
+                '|' => TokenKind::Symbol(read_quoted(
+                    chars,
+                    '|',
+                    SexprSyntaxError::UnterminatedSymbol,
+                )?),
+                ';' => {
+                    // Line comments run up to, but not including, the end of
+                    // the line, which might be a lone `\r` in old Mac files.
+                    while chars.next_if(|c| c != '\n' && c != '\r').is_some() {}
+
+                    if !self.comments {
+                        continue;
+                    }
+
+                    TokenKind::Comment(&chars.code[start..chars.offset])
+                }
+                '#' if chars.next_is('(') => {
+                    chars.next();
+                    chars.next();
+                    TokenKind::LVector
+                }
+                '#' if chars.next_is('|') => {
+                    skip_block_comment(chars)?;
+
+                    if !self.comments {
+                        continue;
+                    }
+
+                    TokenKind::Comment(&chars.code[start..chars.offset])
+                }
+                '#' if chars.next_is(';') => {
+                    chars.next();
+                    chars.next();
+                    TokenKind::DatumComment
+                }
+                '#' if chars.next_is('\\') => {
+                    chars.next();
+                    chars.next();
+
+                    // The character straight after `#\` belongs to the literal
+                    // even when it would otherwise end the token, as in `#\(`,
+                    // but only names like `#\space` carry on past it.
+                    if matches!(chars.next(), Some(c) if is_atom_char(c)) {
+                        while chars.next_if(is_atom_char).is_some() {}
+                    }
+
+                    TokenKind::Atom(&chars.code[start..chars.offset])
+                }
+                _ if c.is_whitespace() => {
+                    chars.next();
+                    continue;
+                }
+                _ => {
+                    while chars.next_if(is_atom_char).is_some() {}
+
+                    let atom = &chars.code[start..chars.offset];
+
+                    if atom == "." {
+                        TokenKind::Dot
+                    } else {
+                        TokenKind::Atom(atom)
+                    }
+                }
+            };
+
+            return Ok(Some(Token {
+                kind,
+                span: start..chars.offset,
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, SexprSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let token = self.next_token().transpose();
+        self.failed = matches!(token, Some(Err(_)));
+
+        token
     }
+}
+
+/// Reads a string literal or pipe-quoted symbol, whichever `quote` starts,
+/// decoding any escape sequences. Contents without any escapes are borrowed
+/// straight from the source.
+fn read_quoted<'a>(
+    chars: &mut Scanner<'a>,
+    quote: char,
+    unterminated: fn(Position) -> SexprSyntaxError,
+) -> Result<Cow<'a, str>, SexprSyntaxError> {
+    let code = chars.code;
+    let start = chars.offset;
+    chars.next();
+
+    let contents_start = chars.offset;
+    // Only allocated once an escape sequence means the contents can no
+    // longer simply be borrowed.
+    let mut decoded: Option<String> = None;
+
+    loop {
+        let escape_start = chars.offset;
+
+        match chars.next() {
+            Some(c) if c == quote => {
+                return Ok(match decoded {
+                    Some(decoded) => Cow::Owned(decoded),
+                    None => Cow::Borrowed(&code[contents_start..escape_start]),
+                })
+            }
+            Some('\\') => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('\\') => '\\',
+                    Some(c) if c == quote => quote,
+                    Some(kind @ ('x' | 'u')) => match read_hex_escape(chars, kind) {
+                        Some(c) => c,
+                        None => {
+                            return Err(SexprSyntaxError::InvalidEscape(
+                                kind,
+                                chars.locate(escape_start),
+                            ))
+                        }
+                    },
+                    Some(other) => {
+                        return Err(SexprSyntaxError::InvalidEscape(
+                            other,
+                            chars.locate(escape_start),
+                        ))
+                    }
+                    None => break,
+                };
+
+                decoded
+                    .get_or_insert_with(|| code[contents_start..escape_start].to_owned())
+                    .push(escaped);
+            }
+            Some(c) => {
+                if let Some(decoded) = &mut decoded {
+                    decoded.push(c);
+                }
+            }
+            None => break,
+        }
+    }
+
+    Err(unterminated(chars.locate(start)))
+}
+
+/// Decodes the rest of a `\xHH` or `\u{H...}` escape, after its `x` or
+/// `u`. As in Rust, `\x` takes exactly two hex digits and only reaches
+/// ASCII, while `\u` takes one to six between braces, naming any Unicode
+/// scalar value.
+fn read_hex_escape(chars: &mut Scanner, kind: char) -> Option<char> {
+    let braced = kind == 'u';
+
+    if braced && !chars.next_if_eq('{') {
+        return None;
+    }
+
+    let max_digits = if braced { 6 } else { 2 };
+    let mut value = 0;
+    let mut digits = 0;
+
+    while digits < max_digits {
+        match chars.next_if(|c| c.is_ascii_hexdigit()) {
+            Some(c) => value = value * 16 + c.to_digit(16)?,
+            None => break,
+        }
+
+        digits += 1;
+    }
+
+    if braced {
+        if digits == 0 || !chars.next_if_eq('}') {
+            return None;
+        }
+    } else if digits != 2 || value > 0x7f {
+        return None;
+    }
+
+    char::from_u32(value)
+}
+
+/// Skips over a `#| ... |#` block comment, which may itself contain nested
+/// block comments.
+fn skip_block_comment(chars: &mut Scanner) -> Result<(), SexprSyntaxError> {
+    let start = chars.offset;
+    let mut depth = 0;
+
+    while let Some(c) = chars.next() {
+        if c == '#' && chars.next_if_eq('|') {
+            depth += 1;
+        } else if c == '|' && chars.next_if_eq('#') {
+            depth -= 1;
+
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(SexprSyntaxError::UnterminatedComment(chars.locate(start)))
+}
+
+const BOM: char = '\u{FEFF}';
+
+/// An iterator over the characters of the source that keeps track of the
+/// byte offset of the next character it will yield.
+#[derive(Clone)]
+struct Scanner<'a> {
+    code: &'a str,
+    chars: Peekable<Chars<'a>>,
+    offset: usize,
+}
+
+impl<'a> Scanner<'a> {
+    /// Starts scanning from the beginning of `code`, after the byte-order
+    /// mark some editors put at the start of a file, if there is one. Offsets
+    /// still count from the very start, so they index into `code` as given.
+    fn new(code: &'a str) -> Self {
+        let offset = if code.starts_with(BOM) {
+            BOM.len_utf8()
+        } else {
+            0
+        };
+
+        Self::starting_at(code, offset)
+    }
+
+    fn starting_at(code: &'a str, offset: usize) -> Self {
+        Self {
+            code,
+            chars: code[offset..].chars().peekable(),
+            offset,
+        }
+    }
+
+    fn locate(&self, offset: usize) -> Position {
+        Position::locate(self.code, offset)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next_if(&mut self, func: impl FnOnce(char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if func(c) => self.next(),
+            _ => None,
+        }
+    }
+
+    fn next_if_eq(&mut self, expected: char) -> bool {
+        self.next_if(|c| c == expected).is_some()
+    }
+
+    /// Checks whether the character after the one currently peeked is `c`.
+    fn next_is(&self, c: char) -> bool {
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+
+        lookahead.peek() == Some(&c)
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+
+        Some(c)
+    }
+}
+
+/// Re-encodes the characters `read_quoted` decodes, so that a displayed
+/// string or pipe-quoted symbol parses back to the same contents. Other
+/// control characters are written as hex escapes, so they can be seen.
+fn escape_quoted(s: &str, quote: char) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            _ if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // Writing to a `String` can't fail.
+            _ if c.is_ascii_control() => {
+                let _ = write!(escaped, "\\x{:02x}", c as u32);
+            }
+            _ if c.is_control() => {
+                let _ = write!(escaped, "\\u{{{:x}}}", c as u32);
+            }
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Whether a symbol can be written as its bare name and still read back as
+/// the same symbol, rather than as a number, say, or as several tokens.
+fn is_bare_symbol(symbol: Symbol) -> bool {
+    let name = symbol.as_str();
+    let mut chars = name.chars();
+
+    // Nothing else starts with a letter, so most names can skip parsing.
+    if matches!(chars.next(), Some(c) if c.is_alphabetic()) && chars.all(is_atom_char) {
+        return true;
+    }
+
+    parse_sexpr(name).is_ok_and(|node| node == Node::Atom(Atom::Symbol(symbol)))
+}
+
+fn is_atom_char(c: char) -> bool {
+    !c.is_whitespace() && !is_delimiter(c)
+}
+
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '(' | ')' | '[' | ']' | '"' | ';' | '\'' | '`' | ',')
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SexprSyntaxError {
+    Empty,
+    /// The input ended before a list was closed or before the datum after a
+    /// reader macro. The position is that of the outermost one left open.
+    UnexpectedEof(Position),
+    /// A `)` or `]` with nothing before it to close.
+    UnexpectedCloseParen(Position),
+    /// A `)` closing a `[`, or a `]` closing a `(` or `#(`. `open` is where
+    /// the form being closed was opened.
+    MismatchedBracket {
+        open: Position,
+        close: Position,
+    },
+    /// More input after the single form `parse_sexpr` expects.
+    TrailingTokens(Position),
+    UnterminatedString(Position),
+    /// A `|` starting a symbol, with no `|` after it to end it.
+    UnterminatedSymbol(Position),
+    InvalidEscape(char, Position),
+    UnterminatedComment(Position),
+    DanglingReaderMacro(Position),
+    /// A reader macro, when `ParseOptions::allow_reader_macros` is off.
+    DisabledReaderMacro(Position),
+    InvalidDottedPair(Position),
+    InvalidNumber(String, Position),
+    /// An integer literal too large for an `Int`. With the `bigint` feature
+    /// these become `BigInt`s instead, except in rationals.
+    IntegerOverflow(String, Position),
+    InvalidChar(String, Position),
+    /// A symbol with a character in it that `ParseOptions::strict_symbols`
+    /// doesn't allow.
+    InvalidSymbol(String, Position),
+    DepthLimitExceeded(Position),
+}
+
+impl SexprSyntaxError {
+    /// Where in the source the error was found, if it was found anywhere in
+    /// particular.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::Empty => None,
+            Self::UnexpectedEof(p)
+            | Self::UnexpectedCloseParen(p)
+            | Self::MismatchedBracket { close: p, .. }
+            | Self::TrailingTokens(p)
+            | Self::UnterminatedString(p)
+            | Self::UnterminatedSymbol(p)
+            | Self::InvalidEscape(_, p)
+            | Self::UnterminatedComment(p)
+            | Self::DanglingReaderMacro(p)
+            | Self::DisabledReaderMacro(p)
+            | Self::InvalidDottedPair(p)
+            | Self::InvalidNumber(_, p)
+            | Self::IntegerOverflow(_, p)
+            | Self::InvalidChar(_, p)
+            | Self::InvalidSymbol(_, p)
+            | Self::DepthLimitExceeded(p) => Some(*p),
+        }
+    }
+
+    /// A short, fixed name for the kind of error, for code that needs to
+    /// tell kinds apart without matching on the variants or the message.
+    /// These stay the same from one version to the next, and a new variant
+    /// gets a new one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::UnexpectedEof(_) => "unexpected-eof",
+            Self::UnexpectedCloseParen(_) => "unmatched-paren",
+            Self::MismatchedBracket { .. } => "mismatched-bracket",
+            Self::TrailingTokens(_) => "trailing-tokens",
+            Self::UnterminatedString(_) => "unterminated-string",
+            Self::UnterminatedSymbol(_) => "unterminated-symbol",
+            Self::InvalidEscape(..) => "invalid-escape",
+            Self::UnterminatedComment(_) => "unterminated-comment",
+            Self::DanglingReaderMacro(_) => "dangling-reader-macro",
+            Self::DisabledReaderMacro(_) => "disabled-reader-macro",
+            Self::InvalidDottedPair(_) => "invalid-dotted-pair",
+            Self::InvalidNumber(..) => "invalid-number",
+            Self::IntegerOverflow(..) => "integer-overflow",
+            Self::InvalidChar(..) => "invalid-char",
+            Self::InvalidSymbol(..) => "invalid-symbol",
+            Self::DepthLimitExceeded(_) => "depth-limit-exceeded",
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SexprSyntaxError {}
+
+impl fmt::Display for SexprSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Empty S-expression provided"),
+            Self::UnexpectedEof(p) => {
+                write!(f, "Input ended before the form at {} was finished", p)
+            }
+            Self::UnexpectedCloseParen(p) => {
+                write!(f, "Unexpected closing paren found at {}", p)
+            }
+            Self::MismatchedBracket { open, close } => write!(
+                f,
+                "Closing bracket at {} does not match the opening one at {}",
+                close, open
+            ),
+            Self::TrailingTokens(p) => {
+                write!(f, "Unexpected input after the end of the form at {}", p)
+            }
+            Self::UnterminatedString(p) => {
+                write!(f, "Unterminated string literal found at {}", p)
+            }
+            Self::UnterminatedSymbol(p) => {
+                write!(f, "Unterminated pipe-quoted symbol found at {}", p)
+            }
+            Self::InvalidEscape(c, p) => {
+                write!(f, "Invalid escape sequence \\{} in string at {}", c, p)
+            }
+            Self::UnterminatedComment(p) => {
+                write!(f, "Unterminated block comment found at {}", p)
+            }
+            Self::DanglingReaderMacro(p) => {
+                write!(f, "Reader macro is not followed by a datum at {}", p)
+            }
+            Self::DisabledReaderMacro(p) => {
+                write!(f, "Reader macro found at {} while they are disabled", p)
+            }
+            Self::InvalidDottedPair(p) => {
+                write!(f, "Malformed dotted pair found at {}", p)
+            }
+            Self::InvalidNumber(n, p) => {
+                write!(f, "Invalid number literal {} at {}", n, p)
+            }
+            Self::IntegerOverflow(n, p) => {
+                write!(f, "Integer literal {} is too large at {}", n, p)
+            }
+            Self::InvalidChar(c, p) => {
+                write!(f, "Invalid character literal {} at {}", c, p)
+            }
+            Self::InvalidSymbol(s, p) => {
+                write!(f, "Invalid symbol {} at {}", s, p)
+            }
+            Self::DepthLimitExceeded(p) => {
+                write!(f, "Maximum nesting depth exceeded at {}", p)
+            }
+        }
+    }
+}
+
+/// A location in the source code. Lines and columns start from 1, and
+/// columns count characters rather than bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    /// The byte offset from the start of the source.
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// Finds the line and column of the byte `offset` into `code`. A
+    /// byte-order mark at the start doesn't take up a column.
+    fn locate(code: &str, offset: usize) -> Self {
+        let before = &code[..offset];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        let line = &before[line_start..];
+        let line = match line_start {
+            0 => line.strip_prefix(BOM).unwrap_or(line),
+            _ => line,
+        };
+
+        Self {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: line.chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Nodes can be used as keys in a `HashSet` or `HashMap`, with the same
+/// caveat about `NaN` as for `Atom`.
+///
+/// A dotted list is a chain of pairs nested as deeply as it has elements,
+/// however few parens it was written with, so dropping, cloning, comparing
+/// and hashing follow the tails of pairs in a loop rather than recursing.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Node {
+    List(Vec<Node>),
+    /// A cons cell whose tail is not necessarily a list, as in `(1 . 2)`.
+    /// Improper lists such as `(a b . c)` are chains of pairs.
+    Pair(Box<Node>, Box<Node>),
+    /// A vector literal such as `#(1 2 3)`. Unlike a list it can't be dotted.
+    Vector(Vec<Node>),
+    Atom(Atom),
+}
+
+/// Shorthands for building nodes, mostly for writing out ASTs by hand.
+impl Node {
+    pub fn symbol(name: &str) -> Self {
+        Self::Atom(Atom::Symbol(Symbol::new(name)))
+    }
+
+    pub fn string(s: &str) -> Self {
+        Self::Atom(Atom::Str(s.to_owned()))
+    }
+
+    pub fn int(i: i64) -> Self {
+        Self::Atom(Atom::Int(i))
+    }
+
+    pub fn float(f: Float) -> Self {
+        Self::Atom(Atom::Float(f))
+    }
+
+    pub fn bool(b: bool) -> Self {
+        Self::Atom(Atom::Bool(b))
+    }
+
+    pub fn list(elements: impl IntoIterator<Item = Node>) -> Self {
+        elements.into_iter().collect()
+    }
+}
+
+impl Node {
+    /// Returns the elements of a list, or `None` for atoms and pairs.
+    pub fn as_list(&self) -> Option<&[Node]> {
+        match self {
+            Self::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the atom this node holds, or `None` for lists, pairs and
+    /// vectors.
+    pub fn as_atom(&self) -> Option<&Atom> {
+        match self {
+            Self::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements after the head of a list whose head is the symbol
+    /// `head`, as in the arguments of a call to it.
+    pub fn match_list(&self, head: &str) -> Option<&[Node]> {
+        match self.as_list()? {
+            [Node::Atom(Atom::Symbol(name)), rest @ ..] if *name == head => Some(rest),
+            _ => None,
+        }
+    }
+
+    /// Like `match_list`, but only for lists with exactly `arity` elements
+    /// after the head.
+    pub fn match_form(&self, head: &str, arity: usize) -> Option<&[Node]> {
+        self.match_list(head).filter(|rest| rest.len() == arity)
+    }
+
+    /// Iterates over the elements of a list or vector. For a chain of pairs
+    /// such as `(a b . c)` that's each element followed by the tail, and
+    /// atoms have no children at all.
+    pub fn children(&self) -> impl Iterator<Item = &Node> {
+        let (elements, pairs): (&[Node], _) = match self {
+            Self::List(v) | Self::Vector(v) => (v, None),
+            Self::Pair(..) => (&[], Some(self)),
+            Self::Atom(_) => (&[], None),
+        };
+
+        Children {
+            elements: elements.iter(),
+            pairs,
+        }
+    }
+
+    /// Compares two trees like `==` does, except that floats are compared by
+    /// their bits. That makes `NaN` equal to itself, so trees containing it
+    /// can still be deduplicated, at the cost of treating `0.0` and `-0.0` as
+    /// different.
+    pub fn structurally_eq(&self, other: &Node) -> bool {
+        let (mut a, mut b) = (self, other);
+
+        loop {
+            match (a, b) {
+                (Self::Pair(car_a, cdr_a), Self::Pair(car_b, cdr_b)) => {
+                    if !car_a.structurally_eq(car_b) {
+                        return false;
+                    }
+
+                    a = cdr_a;
+                    b = cdr_b;
+                }
+                (Self::List(a), Self::List(b)) | (Self::Vector(a), Self::Vector(b)) => {
+                    return a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structurally_eq(b))
+                }
+                (Self::Atom(a), Self::Atom(b)) => return a.structurally_eq(b),
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // Detach each tail before the pair holding it is dropped, so that
+        // every pair is dropped with an empty list as its tail.
+        let mut tail = match self {
+            Self::Pair(_, cdr) => mem::replace(&mut **cdr, Self::List(Vec::new())),
+            _ => return,
+        };
+
+        while let Self::Pair(_, cdr) = &mut tail {
+            tail = mem::replace(&mut **cdr, Self::List(Vec::new()));
+        }
+    }
+}
+
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        match self {
+            Self::List(v) => Self::List(v.clone()),
+            Self::Vector(v) => Self::Vector(v.clone()),
+            Self::Atom(a) => Self::Atom(a.clone()),
+            Self::Pair(..) => {
+                let mut cars = vec![];
+                let mut tail = self;
+
+                while let Self::Pair(car, cdr) = tail {
+                    cars.push(car);
+                    tail = cdr;
+                }
+
+                cars.into_iter().rev().fold(tail.clone(), |cdr, car| {
+                    Self::Pair(car.clone(), Box::new(cdr))
+                })
+            }
+        }
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        let (mut a, mut b) = (self, other);
+
+        loop {
+            match (a, b) {
+                (Self::Pair(car_a, cdr_a), Self::Pair(car_b, cdr_b)) => {
+                    if car_a != car_b {
+                        return false;
+                    }
+
+                    a = cdr_a;
+                    b = cdr_b;
+                }
+                (Self::List(a), Self::List(b)) | (Self::Vector(a), Self::Vector(b)) => {
+                    return a == b
+                }
+                (Self::Atom(a), Self::Atom(b)) => return a == b,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut node = self;
+
+        loop {
+            mem::discriminant(node).hash(state);
+
+            match node {
+                Self::Pair(car, cdr) => {
+                    car.hash(state);
+                    node = cdr;
+                }
+                Self::List(v) | Self::Vector(v) => return v.hash(state),
+                Self::Atom(a) => return a.hash(state),
+            }
+        }
+    }
+}
+
+struct Children<'a> {
+    elements: slice::Iter<'a, Node>,
+    /// What's left of a chain of pairs, which is the tail once the last pair
+    /// has been walked past.
+    pairs: Option<&'a Node>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        if let Some(element) = self.elements.next() {
+            return Some(element);
+        }
+
+        match self.pairs.take()? {
+            Node::Pair(car, cdr) => {
+                self.pairs = Some(cdr);
+                Some(car)
+            }
+            tail => Some(tail),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::List(v) => write!(
+                f,
+                "({})",
+                v.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Self::Vector(v) => write!(
+                f,
+                "#({})",
+                v.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Self::Pair(car, cdr) => {
+                write!(f, "({}", car)?;
+
+                let mut tail = cdr;
+
+                while let Self::Pair(car, cdr) = &**tail {
+                    write!(f, " {}", car)?;
+                    tail = cdr;
+                }
+
+                write!(f, " . {})", tail)
+            }
+            Self::Atom(a) => write!(f, "{}", a),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Atom {
+    /// Always 64 bits wide, whatever the target's pointer size, so that a
+    /// literal fits or overflows the same way on every platform.
+    Int(i64),
+    /// An integer literal too large to fit in an `Int`.
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
+    Float(Float),
+    /// An exact fraction in lowest terms. `den` is always greater than 1,
+    /// since anything else would be an `Int`.
+    Rational {
+        num: i64,
+        den: i64,
+    },
+    Symbol(Symbol),
+    /// A self-evaluating name written with a leading colon, as in `:key`.
+    /// The colon isn't part of the stored name.
+    Keyword(String),
+    Str(String),
+    Char(char),
+    Bool(bool),
+    /// The symbol `nil`, read as a value of its own rather than a symbol
+    /// when `ParseOptions::distinct_nil` is on. It's distinct from the
+    /// empty list `()` too, which is always an empty `Node::List`.
+    Nil,
+    /// An integer written with a radix prefix, as in `#xFF`, read as one of
+    /// these rather than an `Int` when `ParseOptions::keep_radix` is on, so
+    /// that it displays the way it was written.
+    RadixInt(i64, Radix),
+}
+
+/// The radixes an integer literal can be written in besides decimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Radix {
+    Binary,
+    Octal,
+    Hex,
+}
+
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Self::Binary => 2,
+            Self::Octal => 8,
+            Self::Hex => 16,
+        }
+    }
+}
+
+impl Atom {
+    /// Compares two atoms like `==` does, except that floats are compared by
+    /// their bits. See `Node::structurally_eq`.
+    pub fn structurally_eq(&self, other: &Atom) -> bool {
+        match (self, other) {
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => self == other,
+        }
+    }
+
+    /// Compares two numbers by their value, whatever kind of number each one
+    /// is. Where `==` is structural and so tells `2` and `2.0` apart, this
+    /// makes them equal, as a Lisp's `=` does. Integers and floats are
+    /// compared exactly, but a rational is converted to a float to compare
+    /// it with one. Anything that isn't a number, and `NaN`, isn't
+    /// numerically equal to anything.
+    pub fn numeric_eq(&self, other: &Atom) -> bool {
+        match (self, other) {
+            (Self::RadixInt(i, _), other) | (other, Self::RadixInt(i, _)) => {
+                Self::Int(*i).numeric_eq(other)
+            }
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b,
+            (Self::Int(i), Self::Float(f)) | (Self::Float(f), Self::Int(i)) => {
+                int_eq_float(*i, widen(*f))
+            }
+            (Self::Rational { num: a, den: b }, Self::Rational { num: c, den: d }) => {
+                (a, b) == (c, d)
+            }
+            (Self::Rational { num, den }, Self::Float(f))
+            | (Self::Float(f), Self::Rational { num, den }) => {
+                (*num as f64 / *den as f64) as Float == *f
+            }
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(a), Self::BigInt(b)) => a == b,
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b), Self::Int(i)) | (Self::Int(i), Self::BigInt(b)) => {
+                *b == BigInt::from(*i)
+            }
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b), Self::Float(f)) | (Self::Float(f), Self::BigInt(b)) => {
+                // A whole float prints as exactly the integer it holds.
+                f.fract() == 0.0 && format!("{:.0}", f).parse::<BigInt>().is_ok_and(|f| f == *b)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Accessors for the contents of each kind of atom, giving `None` for any
+/// other kind.
+impl Atom {
+    /// The name of this kind of atom, which is the name of its variant, as
+    /// in `"Int"` or `"Symbol"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Int(_) => "Int",
+            #[cfg(feature = "bigint")]
+            Self::BigInt(_) => "BigInt",
+            Self::Float(_) => "Float",
+            Self::Rational { .. } => "Rational",
+            Self::Symbol(_) => "Symbol",
+            Self::Keyword(_) => "Keyword",
+            Self::Str(_) => "Str",
+            Self::Char(_) => "Char",
+            Self::Bool(_) => "Bool",
+            Self::Nil => "Nil",
+            Self::RadixInt(..) => "RadixInt",
+        }
+    }
+
+    /// Gives the value of an `Int` or a `RadixInt`.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) | Self::RadixInt(i, _) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<Float> {
+        match self {
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Gives the value of any integer, rational or float as a float, which
+    /// rounds integers too large for an `f64` to hold exactly.
+    pub fn as_float_lossy(&self) -> Option<f64> {
+        match self {
+            Self::Int(i) | Self::RadixInt(i, _) => Some(*i as f64),
+            Self::Rational { num, den } => Some(*num as f64 / *den as f64),
+            Self::Float(f) => Some(widen(*f)),
+            _ => None,
+        }
+    }
+
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self {
+            Self::Symbol(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_keyword(&self) -> Option<&str> {
+        match self {
+            Self::Keyword(k) => Some(k),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Self::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a `Float` to an `f64`, which is exact whichever type it is.
+// Without `small-float` this converts an `f64` to itself.
+#[allow(clippy::useless_conversion)]
+fn widen(f: Float) -> f64 {
+    f.into()
+}
+
+/// Whether an integer and a float hold exactly the same value. Converting the
+/// integer would round it, making `2^53 + 1` equal to `2^53`, so this checks
+/// whether the float is a whole number in range and converts that instead.
+fn int_eq_float(i: i64, f: f64) -> bool {
+    (-I64_LIMIT..I64_LIMIT).contains(&f) && f as i64 == i && i as f64 == f
+}
+
+/// 2^63, the first float too large for an `i64`.
+const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Orders an integer against a float exactly, in the same way `int_eq_float`
+/// compares them, or gives `None` for `NaN`. A float in range is compared by
+/// its integer part and then by whether it has a fraction left over, and one
+/// out of range is larger or smaller than every `i64`.
+#[cfg(feature = "std")]
+pub(crate) fn cmp_int_float(i: i64, f: Float) -> Option<cmp::Ordering> {
+    let f = widen(f);
+
+    if f.is_nan() {
+        None
+    } else if f >= I64_LIMIT {
+        Some(cmp::Ordering::Less)
+    } else if f < -I64_LIMIT {
+        Some(cmp::Ordering::Greater)
+    } else {
+        // Truncating is exact in range, and a float with a fraction is small
+        // enough that its integer part converts back exactly too.
+        let whole = f as i64;
+
+        Some(
+            i.cmp(&whole)
+                .then_with(|| (whole as f64).partial_cmp(&f).unwrap()),
+        )
+    }
+}
+
+/// This is structural equality, so `2` and `2.0` are different atoms; see
+/// `Atom::numeric_eq` for comparing numbers by value. Strictly speaking
+/// `NaN` breaks the rule that everything equals itself,
+/// but the collections that need `Eq` only go wrong for the `NaN`s, as
+/// described below.
+impl Eq for Atom {}
+
+/// Floats are hashed by their bits, except that `-0.0` hashes the same as
+/// `0.0`, since the two are equal. `NaN` isn't equal to anything, itself
+/// included, so a node holding one can go into a `HashSet` or `HashMap` but
+/// can never be looked up there again.
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Int(i) => i.hash(state),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(i) => i.hash(state),
+            Self::Float(f) => {
+                let f = if *f == 0.0 { 0.0 } else { *f };
+                f.to_bits().hash(state)
+            }
+            Self::Rational { num, den } => (num, den).hash(state),
+            Self::Symbol(s) => s.hash(state),
+            Self::Keyword(k) => k.hash(state),
+            Self::Str(s) => s.hash(state),
+            Self::Char(c) => c.hash(state),
+            Self::Bool(b) => b.hash(state),
+            Self::Nil => {}
+            Self::RadixInt(i, radix) => (i, radix).hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{}", i),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(i) => write!(f, "{}", i),
+            // `Debug` writes these as `NaN` and `inf`, which read back as symbols.
+            Self::Float(fl) if fl.is_nan() => write!(f, "+nan.0"),
+            Self::Float(fl) if fl.is_infinite() => {
+                write!(f, "{}inf.0", if *fl > 0.0 { '+' } else { '-' })
+            }
+            // Unlike `Display`, `Debug` always writes a `.` or an exponent,
+            // so that a float like `2.0` doesn't read back as an integer.
+            Self::Float(fl) => write!(f, "{:?}", fl),
+            Self::Rational { num, den } => write!(f, "{}/{}", num, den),
+            Self::Symbol(s) if is_bare_symbol(*s) => write!(f, "{}", s),
+            Self::Symbol(s) => write!(f, "|{}|", escape_quoted(s.as_str(), '|')),
+            Self::Keyword(k) => write!(f, ":{}", k),
+            Self::Str(s) => write!(f, "\"{}\"", escape_quoted(s, '"')),
+            Self::Char(' ') => write!(f, "#\\space"),
+            Self::Char('\n') => write!(f, "#\\newline"),
+            Self::Char('\t') => write!(f, "#\\tab"),
+            Self::Char(c) => write!(f, "#\\{}", c),
+            Self::Bool(true) => write!(f, "#t"),
+            Self::Bool(false) => write!(f, "#f"),
+            Self::Nil => write!(f, "nil"),
+            Self::RadixInt(i, radix) => {
+                let sign = if *i < 0 { "-" } else { "" };
+                let digits = i.unsigned_abs();
+
+                match radix {
+                    Radix::Binary => write!(f, "#b{}{:b}", sign, digits),
+                    Radix::Octal => write!(f, "#o{}{:o}", sign, digits),
+                    Radix::Hex => write!(f, "#x{}{:X}", sign, digits),
+                }
+            }
+        }
+    }
+}
+
+impl From<i64> for Atom {
+    fn from(i: i64) -> Self {
+        Self::Int(i)
+    }
+}
+
+impl From<Float> for Atom {
+    fn from(f: Float) -> Self {
+        Self::Float(f)
+    }
+}
+
+/// Strings convert into symbols rather than string literals, since symbols
+/// are by far the more common of the two in code.
+impl From<&str> for Atom {
+    fn from(name: &str) -> Self {
+        Self::Symbol(Symbol::new(name))
+    }
+}
+
+impl From<String> for Atom {
+    fn from(name: String) -> Self {
+        Self::Symbol(Symbol::new(&name))
+    }
+}
+
+impl From<Symbol> for Atom {
+    fn from(symbol: Symbol) -> Self {
+        Self::Symbol(symbol)
+    }
+}
+
+impl From<bool> for Atom {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<char> for Atom {
+    fn from(c: char) -> Self {
+        Self::Char(c)
+    }
+}
+
+impl From<Atom> for Node {
+    fn from(atom: Atom) -> Self {
+        Self::Atom(atom)
+    }
+}
+
+/// Lets anything that converts into an `Atom` convert straight into a
+/// `Node` too.
+macro_rules! node_from_atom {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Node {
+                fn from(value: $t) -> Self {
+                    Self::Atom(value.into())
+                }
+            }
+        )*
+    };
+}
+
+node_from_atom!(i64, Float, &str, String, Symbol, bool, char);
+
+/// Like converting from a `&str`, comparing with one treats it as the name of
+/// a symbol, so only a symbol of that name is equal to it, not a string or
+/// keyword.
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Self::Symbol(s) if s == other)
+    }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Equal to a name only when the node is a symbol of that name.
+impl PartialEq<str> for Node {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Self::Atom(atom) if atom == other)
+    }
+}
+
+impl PartialEq<&str> for Node {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Collects nodes into a `Node::List`.
+impl iter::FromIterator<Node> for Node {
+    fn from_iter<I: IntoIterator<Item = Node>>(iter: I) -> Self {
+        Self::List(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_complete, is_complete_with, parse_one, parse_one_with, parse_program,
+        parse_program_recover, parse_program_recover_with, parse_program_spanned,
+        parse_program_spanned_with, parse_program_with, parse_sexpr, parse_sexpr_spanned,
+        parse_sexpr_spanned_with, parse_sexpr_with, parse_sexpr_with_max_depth, tokenize, Atom::*,
+        Node, Node::*, ParseOptions, Parser, Position, Radix, SexprSyntaxError, Spans, Token,
+        TokenKind,
+    };
+
+    fn pos(offset: usize, line: usize, column: usize) -> Position {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn empty_err() {
+        assert_eq!(parse_sexpr("").unwrap_err(), SexprSyntaxError::Empty);
+    }
+
+    #[test]
+    fn parse_one_gives_the_rest() {
+        let (node, rest) = parse_one("(+ 1 2) rest").unwrap();
+
+        assert_eq!(node, parse_sexpr("(+ 1 2)").unwrap());
+        assert_eq!(rest, " rest");
+
+        let mut code = " a 'b ; c\n(d . e)";
+        let mut forms = vec![];
+
+        while let Ok((node, rest)) = parse_one(code) {
+            forms.push(node);
+            code = rest;
+        }
+
+        assert_eq!(forms, parse_program(" a 'b ; c\n(d . e)").unwrap());
+        assert_eq!(code, "");
+        assert_eq!(parse_one(" ; c\n").unwrap_err(), SexprSyntaxError::Empty);
+        assert_eq!(parse_one("a ) b").unwrap(), (Node::symbol("a"), " ) b"));
+        assert_eq!(
+            parse_one("(a").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1))
+        );
+    }
+
+    #[test]
+    fn whitespace_only_is_empty() {
+        for code in ["   ", "\t", "\n\r\n", " \t\n "] {
+            assert_eq!(parse_sexpr(code).unwrap_err(), SexprSyntaxError::Empty);
+            assert_eq!(parse_program(code).unwrap(), Vec::<Node>::new());
+            assert_eq!(tokenize(code).count(), 0);
+        }
+    }
+
+    #[test]
+    fn whitespace_between_tokens_is_ignored() {
+        let compact = parse_sexpr("(+ 1 2)").unwrap();
+
+        assert_eq!(parse_sexpr("  (+\n 1\t 2)  ").unwrap(), compact);
+        assert_eq!(parse_sexpr("\n\n(  +   1\r\n\t\t2\n)\t").unwrap(), compact);
+        assert_eq!(
+            parse_program(" a \n\t b\n").unwrap(),
+            [Node::symbol("a"), Node::symbol("b")]
+        );
+    }
+
+    #[test]
+    fn atom_int() {
+        assert_eq!(parse_sexpr("1").unwrap(), Node::int(1));
+    }
+
+    #[test]
+    fn atom_float() {
+        assert_eq!(parse_sexpr("1.5").unwrap(), Node::float(1.5));
+    }
+
+    #[test]
+    fn atom_str() {
+        assert_eq!(parse_sexpr("hello").unwrap(), Node::symbol("hello"),)
+    }
+
+    #[test]
+    fn empty_list() {
+        assert_eq!(parse_sexpr("()").unwrap(), Node::list([]),)
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            parse_sexpr("(+ 1 2)").unwrap(),
+            Node::list([Node::symbol("+"), Node::int(1), Node::int(2)]),
+        );
+    }
+
+    #[test]
+    fn single_unmatched_paren_err() {
+        assert_eq!(
+            parse_sexpr("(").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        )
+    }
+
+    #[test]
+    fn single_unmatched_back_paren_err() {
+        assert_eq!(
+            parse_sexpr(")").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(0, 1, 1)),
+        )
+    }
+
+    #[test]
+    fn unmatched_paren_err() {
+        assert_eq!(
+            parse_sexpr("(+ 1 2").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn unmatched_front_paren_err() {
+        assert_eq!(
+            parse_sexpr("+ 1 2)").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(2, 1, 3)),
+        )
+    }
+
+    #[test]
+    fn trailing_tokens_err() {
+        assert_eq!(
+            parse_sexpr("(a) b").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(4, 1, 5)),
+        );
+        assert_eq!(
+            parse_sexpr("a \"b\"").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(2, 1, 3)),
+        );
+        assert_eq!(
+            parse_sexpr("(a) (b)").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(4, 1, 5)),
+        );
+    }
+
+    #[test]
+    fn unmatched_extra_paren_err() {
+        assert_eq!(
+            parse_sexpr("(+ 1 2))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(7, 1, 8)),
+        )
+    }
+
+    #[test]
+    fn unmatched_paren_nested_err() {
+        assert_eq!(
+            parse_sexpr("(+ (+ 3 4 2)").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn unclosed_inner_paren_err() {
+        // The close paren belongs to the inner list, which leaves the outer
+        // one unfinished.
+        assert_eq!(
+            parse_sexpr("(a ( b)").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("(a ( b").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("(a)\n( b").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(4, 2, 1)),
+        );
+        assert_eq!(
+            parse_program("(a)\n( b").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(4, 2, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("(a (b (c d) e").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_program("(x)\n  (a\n (b (c d) e").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(6, 2, 3)),
+        );
+    }
+
+    #[test]
+    fn paren_characters_are_not_close_parens() {
+        assert_eq!(
+            parse_sexpr("(#\\) \")\")").unwrap(),
+            Node::list([Atom(Char(')')), Node::string(")")]),
+        );
+    }
+
+    #[test]
+    fn unmatched_extra_paren_nested_err() {
+        assert_eq!(
+            parse_sexpr("(+ (+ 3 4 2)))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(13, 1, 14)),
+        )
+    }
+
+    #[test]
+    fn basic_nested() {
+        assert_eq!(
+            parse_sexpr("(+ (+ 1 2) 3)").unwrap(),
+            Node::list([
+                Node::symbol("+"),
+                Node::list([Node::symbol("+"), Node::int(1), Node::int(2)]),
+                Node::int(3)
+            ],),
+        );
+    }
+
+    #[test]
+    fn multiple_nested() {
+        assert_eq!(
+            parse_sexpr("(+ (+ 1 2) (+ 3 4))").unwrap(),
+            Node::list([
+                Node::symbol("+"),
+                Node::list([Node::symbol("+"), Node::int(1), Node::int(2)]),
+                Node::list([Node::symbol("+"), Node::int(3), Node::int(4)]),
+            ],),
+        );
+    }
+
+    #[test]
+    fn long_opname_multiple_nested() {
+        assert_eq!(
+            parse_sexpr("(add (add 1 2) (add 3 4))").unwrap(),
+            Node::list([
+                Node::symbol("add"),
+                Node::list([Node::symbol("add"), Node::int(1), Node::int(2),]),
+                Node::list([Node::symbol("add"), Node::int(3), Node::int(4)]),
+            ],),
+        );
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let sexpr = "(car (list 1 (+ 2 3) (* (+ 4 5) 6)))";
+
+        let expected_inner = Node::list([
+            Node::symbol("list"),
+            Node::int(1),
+            Node::list([Node::symbol("+"), Node::int(2), Node::int(3)]),
+            Node::list([
+                Node::symbol("*"),
+                Node::list([Node::symbol("+"), Node::int(4), Node::int(5)]),
+                Node::int(6),
+            ]),
+        ]);
+
+        assert_eq!(
+            parse_sexpr(sexpr).unwrap(),
+            Node::list([Node::symbol("car"), expected_inner,])
+        );
+    }
+
+    #[test]
+    fn many_sibling_lists() {
+        let count = 10_000;
+        let sexpr = format!("({})", "(a (b) c) ".repeat(count));
+        let sibling = parse_sexpr("(a (b) c)").unwrap();
+
+        assert_eq!(
+            parse_sexpr(&sexpr).unwrap(),
+            Node::list(std::iter::repeat_n(sibling, count))
+        );
+    }
+
+    #[test]
+    fn atom_string() {
+        assert_eq!(
+            parse_sexpr("\"hello world\"").unwrap(),
+            Node::string("hello world"),
+        )
+    }
+
+    #[test]
+    fn string_with_parens() {
+        assert_eq!(
+            parse_sexpr("(print \"(not a list)\" 1)").unwrap(),
+            Node::list([
+                Node::symbol("print"),
+                Node::string("(not a list)"),
+                Node::int(1),
+            ]),
+        )
+    }
+
+    #[test]
+    fn string_display_roundtrip() {
+        let node = parse_sexpr("\"a (b) c\"").unwrap();
+
+        assert_eq!(node.to_string(), "\"a (b) c\"");
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn unterminated_string_err() {
+        assert_eq!(
+            parse_sexpr("(print \"hello)").unwrap_err(),
+            SexprSyntaxError::UnterminatedString(pos(7, 1, 8)),
+        )
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            parse_sexpr(r#""line1\nline2\t\"quoted\" \\ \r""#).unwrap(),
+            Node::string("line1\nline2\t\"quoted\" \\ \r"),
+        )
+    }
+
+    #[test]
+    fn escaped_quote_does_not_terminate() {
+        assert_eq!(
+            parse_sexpr(r#"(print "say \"hi)\"")"#).unwrap(),
+            Node::list([Node::symbol("print"), Node::string("say \"hi)\""),]),
+        )
+    }
+
+    #[test]
+    fn string_escapes_display_roundtrip() {
+        let node = Node::string("tab\there\n\"quoted\" back\\slash");
+
+        assert_eq!(node.to_string(), r#""tab\there\n\"quoted\" back\\slash""#);
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn hex_and_unicode_escapes() {
+        assert_eq!(
+            parse_sexpr(r#""\u{263A}""#).unwrap(),
+            Node::string("\u{263a}")
+        );
+        assert_eq!(
+            parse_sexpr(r#""\x41\u{1F600}\u{e9}!""#).unwrap(),
+            Node::string("A\u{1F600}\u{e9}!")
+        );
+        assert_eq!(parse_sexpr(r#"|\x20|"#).unwrap(), Node::symbol(" "));
+
+        for (code, kind, column) in [
+            (r#""\u{110000}""#, 'u', 2),
+            (r#""\u{D800}""#, 'u', 2),
+            (r#""\u{}""#, 'u', 2),
+            (r#""\u263A""#, 'u', 2),
+            (r#""\u{1234567}""#, 'u', 2),
+            (r#""ab\x4""#, 'x', 4),
+            (r#""\x80""#, 'x', 2),
+            (r#""\xg0""#, 'x', 2),
+        ] {
+            assert_eq!(
+                parse_sexpr(code).unwrap_err(),
+                SexprSyntaxError::InvalidEscape(kind, pos(column - 1, 1, column)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn control_characters_display_as_escapes() {
+        let node = Node::string("bell\u{7}, null\0, next line\u{85}, ☺");
+
+        assert_eq!(
+            node.to_string(),
+            r#""bell\x07, null\x00, next line\u{85}, ☺""#
+        );
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn invalid_escape_err() {
+        assert_eq!(
+            parse_sexpr(r#""bad \q escape""#).unwrap_err(),
+            SexprSyntaxError::InvalidEscape('q', pos(5, 1, 6)),
+        )
+    }
+
+    #[test]
+    fn line_comment_end_of_line() {
+        assert_eq!(
+            parse_sexpr("(+ 1 ; the first operand\n 2) ; trailing").unwrap(),
+            Node::list([Node::symbol("+"), Node::int(1), Node::int(2),]),
+        )
+    }
+
+    #[test]
+    fn line_comment_only_line() {
+        assert_eq!(
+            parse_sexpr("; a whole line of commentary\n(+ 1 2)\n;; and another\n").unwrap(),
+            Node::list([Node::symbol("+"), Node::int(1), Node::int(2),]),
+        )
+    }
+
+    #[test]
+    fn line_comment_terminates_symbol() {
+        assert_eq!(
+            parse_sexpr("(foo;bar\n)").unwrap(),
+            Node::list([Node::symbol("foo")]),
+        )
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        assert_eq!(
+            parse_program("(define x ; the answer\r\n  42)\r\n; done\r\n(+ x\r\n1)\r\n").unwrap(),
+            vec![
+                Node::list([Node::symbol("define"), Node::symbol("x"), Node::int(42)]),
+                Node::list([Node::symbol("+"), Node::symbol("x"), Node::int(1)]),
+            ],
+        );
+        assert_eq!(
+            parse_sexpr("(a ; comment\rb)").unwrap(),
+            Node::list([Node::symbol("a"), Node::symbol("b")]),
+        );
+    }
+
+    #[test]
+    fn crlf_error_positions() {
+        assert_eq!(
+            parse_sexpr("(a\r\n  b))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(8, 2, 5)),
+        );
+    }
+
+    #[test]
+    fn unicode_whitespace_separates_tokens() {
+        assert_eq!(
+            parse_sexpr("(a\u{a0}b\u{2003}c\u{3000}; comment\u{a0}(\n\td)").unwrap(),
+            Node::list([
+                Node::symbol("a"),
+                Node::symbol("b"),
+                Node::symbol("c"),
+                Node::symbol("d"),
+            ]),
+        );
+    }
+
+    #[test]
+    fn semicolon_in_string() {
+        assert_eq!(
+            parse_sexpr("(print \"a; b\") ; comment").unwrap(),
+            Node::list([Node::symbol("print"), Node::string("a; b"),]),
+        )
+    }
+
+    #[test]
+    fn comment_only_err() {
+        assert_eq!(
+            parse_sexpr("; nothing to see here").unwrap_err(),
+            SexprSyntaxError::Empty,
+        )
+    }
+
+    #[test]
+    fn block_comment() {
+        assert_eq!(
+            parse_sexpr("(+ 1 #| (+ 2 3) |# 4)").unwrap(),
+            Node::list([Node::symbol("+"), Node::int(1), Node::int(4),]),
+        )
+    }
+
+    #[test]
+    fn nested_block_comment() {
+        assert_eq!(
+            parse_sexpr("#| outer #| inner |# outer |#\n(foo)").unwrap(),
+            Node::list([Node::symbol("foo")]),
+        )
+    }
+
+    #[test]
+    fn block_comment_in_string() {
+        assert_eq!(
+            parse_sexpr("\"#| not a comment |#\"").unwrap(),
+            Node::string("#| not a comment |#"),
+        )
+    }
+
+    #[test]
+    fn unterminated_block_comment_err() {
+        assert_eq!(
+            parse_sexpr("(foo) #| outer #| inner |# never closed").unwrap_err(),
+            SexprSyntaxError::UnterminatedComment(pos(6, 1, 7)),
+        )
+    }
+
+    #[test]
+    fn quote_atom() {
+        assert_eq!(
+            parse_sexpr("'foo").unwrap(),
+            Node::list([Node::symbol("quote"), Node::symbol("foo"),]),
+        )
+    }
+
+    #[test]
+    fn quote_list() {
+        assert_eq!(
+            parse_sexpr("'(1 2 3)").unwrap(),
+            Node::list([
+                Node::symbol("quote"),
+                Node::list([Node::int(1), Node::int(2), Node::int(3)]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn nested_quote() {
+        assert_eq!(
+            parse_sexpr("''x").unwrap(),
+            Node::list([
+                Node::symbol("quote"),
+                Node::list([Node::symbol("quote"), Node::symbol("x"),]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn quote_inside_list() {
+        assert_eq!(
+            parse_sexpr("(cons 'a '(b c))").unwrap(),
+            Node::list([
+                Node::symbol("cons"),
+                Node::list([Node::symbol("quote"), Node::symbol("a"),]),
+                Node::list([
+                    Node::symbol("quote"),
+                    Node::list([Node::symbol("b"), Node::symbol("c"),]),
+                ]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn dangling_quote_err() {
+        assert_eq!(
+            parse_sexpr("(list 1 ')").unwrap_err(),
+            SexprSyntaxError::DanglingReaderMacro(pos(8, 1, 9)),
+        );
+        assert_eq!(
+            parse_sexpr("'").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn quasiquote_unquote() {
+        assert_eq!(
+            parse_sexpr("`(a ,b ,@c)").unwrap(),
+            Node::list([
+                Node::symbol("quasiquote"),
+                Node::list([
+                    Node::symbol("a"),
+                    Node::list([Node::symbol("unquote"), Node::symbol("b"),]),
+                    Node::list([Node::symbol("unquote-splicing"), Node::symbol("c"),]),
+                ]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn unquote_list_inside_list() {
+        assert_eq!(
+            parse_sexpr("(f ,(g x) ,@(h y))").unwrap(),
+            Node::list([
+                Node::symbol("f"),
+                Node::list([
+                    Node::symbol("unquote"),
+                    Node::list([Node::symbol("g"), Node::symbol("x"),]),
+                ]),
+                Node::list([
+                    Node::symbol("unquote-splicing"),
+                    Node::list([Node::symbol("h"), Node::symbol("y"),]),
+                ]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn nested_quasiquote() {
+        assert_eq!(
+            parse_sexpr("(a `(b ,,c))").unwrap(),
+            Node::list([
+                Node::symbol("a"),
+                Node::list([
+                    Node::symbol("quasiquote"),
+                    Node::list([
+                        Node::symbol("b"),
+                        Node::list([
+                            Node::symbol("unquote"),
+                            Node::list([Node::symbol("unquote"), Node::symbol("c"),]),
+                        ]),
+                    ]),
+                ]),
+            ]),
+        )
+    }
+
+    #[test]
+    fn unquote_splicing_without_space() {
+        assert_eq!(
+            parse_sexpr(",@x").unwrap(),
+            Node::list([Node::symbol("unquote-splicing"), Node::symbol("x"),]),
+        );
+        assert_eq!(
+            parse_sexpr(", @x").unwrap(),
+            Node::list([Node::symbol("unquote"), Node::symbol("@x"),]),
+        );
+    }
+
+    #[test]
+    fn dotted_pair() {
+        assert_eq!(
+            parse_sexpr("(1 . 2)").unwrap(),
+            Pair(Box::new(Node::int(1)), Box::new(Node::int(2))),
+        )
+    }
+
+    #[test]
+    fn dots_inside_atoms() {
+        assert_eq!(parse_sexpr(".5").unwrap(), Node::float(0.5));
+        assert_eq!(parse_sexpr("-.5").unwrap(), Node::float(-0.5));
+        assert_eq!(parse_sexpr("foo.bar").unwrap(), Node::symbol("foo.bar"));
+        assert_eq!(
+            parse_sexpr("(a .b ... .5)").unwrap(),
+            Node::list([
+                Node::symbol("a"),
+                Node::symbol(".b"),
+                Node::symbol("..."),
+                Node::float(0.5),
+            ]),
+        );
+        assert_eq!(
+            parse_sexpr("(a . b)").unwrap(),
+            Pair(Box::new(Node::symbol("a")), Box::new(Node::symbol("b"))),
+        );
+        assert_eq!(
+            parse_sexpr("(a.(b))").unwrap(),
+            Node::list([Node::symbol("a."), Node::list([Node::symbol("b")])]),
+        );
+        assert_eq!(
+            parse_sexpr("(a . .5)").unwrap(),
+            Pair(Box::new(Node::symbol("a")), Box::new(Node::float(0.5))),
+        );
+        assert_eq!(
+            parse_sexpr("(a .(b))").unwrap(),
+            Pair(
+                Box::new(Node::symbol("a")),
+                Box::new(Node::list([Node::symbol("b")]))
+            ),
+        );
+    }
+
+    #[test]
+    fn improper_list() {
+        assert_eq!(
+            parse_sexpr("(a b . c)").unwrap(),
+            Pair(
+                Box::new(Node::symbol("a")),
+                Box::new(Pair(
+                    Box::new(Node::symbol("b")),
+                    Box::new(Node::symbol("c")),
+                )),
+            ),
+        )
+    }
+
+    #[test]
+    fn dotted_pair_with_list_tail() {
+        assert_eq!(
+            parse_sexpr("(a . (b c))").unwrap(),
+            Pair(
+                Box::new(Node::symbol("a")),
+                Box::new(Node::list([Node::symbol("b"), Node::symbol("c"),])),
+            ),
+        )
+    }
+
+    #[test]
+    fn dotted_pair_display() {
+        assert_eq!(parse_sexpr("(1 . 2)").unwrap().to_string(), "(1 . 2)");
+        assert_eq!(
+            parse_sexpr("((1 . 2) 3 . 4)").unwrap().to_string(),
+            "((1 . 2) 3 . 4)",
+        );
+    }
+
+    #[test]
+    fn invalid_dotted_pair_err() {
+        let cases = [
+            ("(1 . 2 3)", 3),
+            ("(. 2)", 1),
+            ("(1 .)", 3),
+            ("(1 . 2 . 3)", 3),
+            (".", 0),
+        ];
+
+        for (sexpr, dot) in cases.iter() {
+            assert_eq!(
+                parse_sexpr(sexpr).unwrap_err(),
+                SexprSyntaxError::InvalidDottedPair(pos(*dot, 1, dot + 1)),
+            );
+        }
+    }
+
+    #[test]
+    fn atom_bool() {
+        assert_eq!(parse_sexpr("#t").unwrap(), Node::bool(true));
+        assert_eq!(parse_sexpr("#f").unwrap(), Node::bool(false));
+        assert_eq!(parse_sexpr("#true").unwrap(), Node::bool(true));
+        assert_eq!(parse_sexpr("#false").unwrap(), Node::bool(false));
+    }
+
+    #[test]
+    fn bool_in_if() {
+        assert_eq!(
+            parse_sexpr("(if #t 1 2)").unwrap(),
+            Node::list([
+                Node::symbol("if"),
+                Node::bool(true),
+                Node::int(1),
+                Node::int(2),
+            ]),
+        )
+    }
+
+    #[test]
+    fn bool_display() {
+        assert_eq!(parse_sexpr("(#true #f)").unwrap().to_string(), "(#t #f)");
+    }
+
+    #[test]
+    fn bool_lookalike_is_symbol() {
+        assert_eq!(parse_sexpr("#tru").unwrap(), Node::symbol("#tru"),)
+    }
+
+    #[test]
+    fn atom_keyword() {
+        assert_eq!(
+            parse_sexpr("(make-point :x 1 :y 2)").unwrap(),
+            Node::list([
+                Node::symbol("make-point"),
+                Atom(Keyword("x".to_owned())),
+                Node::int(1),
+                Atom(Keyword("y".to_owned())),
+                Node::int(2),
+            ]),
+        )
+    }
+
+    #[test]
+    fn bare_colon_is_symbol() {
+        assert_eq!(parse_sexpr(":").unwrap(), Node::symbol(":"));
+    }
+
+    #[test]
+    fn keyword_display() {
+        let node = parse_sexpr("(:a ::b)").unwrap();
+
+        assert_eq!(node.to_string(), "(:a ::b)");
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn atom_char() {
+        assert_eq!(parse_sexpr(r"#\a").unwrap(), Atom(Char('a')));
+        assert_eq!(parse_sexpr(r"#\λ").unwrap(), Atom(Char('λ')));
+        assert_eq!(parse_sexpr(r"#\space").unwrap(), Atom(Char(' ')));
+        assert_eq!(parse_sexpr(r"#\newline").unwrap(), Atom(Char('\n')));
+        assert_eq!(parse_sexpr(r"#\tab").unwrap(), Atom(Char('\t')));
+    }
+
+    #[test]
+    fn char_delimiters_are_single_tokens() {
+        assert_eq!(
+            parse_sexpr(r#"(#\( #\) #\; #\" #\  #\s)"#).unwrap(),
+            Node::list([
+                Atom(Char('(')),
+                Atom(Char(')')),
+                Atom(Char(';')),
+                Atom(Char('"')),
+                Atom(Char(' ')),
+                Atom(Char('s')),
+            ]),
+        );
+        assert_eq!(
+            parse_sexpr(r"(#\a)").unwrap(),
+            Node::list([Atom(Char('a'))]),
+        );
+    }
+
+    #[test]
+    fn invalid_char_err() {
+        assert_eq!(
+            parse_sexpr(r"(#\nope)").unwrap_err(),
+            SexprSyntaxError::InvalidChar(r"#\nope".to_owned(), pos(1, 1, 2)),
+        );
+        assert_eq!(
+            parse_sexpr(r"#\").unwrap_err(),
+            SexprSyntaxError::InvalidChar(r"#\".to_owned(), pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn char_display_roundtrip() {
+        let node = parse_sexpr(r"(#\a #\space #\newline #\tab #\( #\#)").unwrap();
+
+        assert_eq!(node.to_string(), r"(#\a #\space #\newline #\tab #\( #\#)");
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn signed_ints() {
+        assert_eq!(parse_sexpr("-5").unwrap(), Node::int(-5));
+        assert_eq!(parse_sexpr("+5").unwrap(), Node::int(5));
+        assert_eq!(parse_sexpr("+7").unwrap(), Node::int(7));
+    }
+
+    #[test]
+    fn ints_are_64_bit() {
+        // Past the range of a 32-bit isize, so this would fail to parse on a
+        // 32-bit target if the width followed the pointer size.
+        assert_eq!(parse_sexpr("4294967296").unwrap(), Node::int(1 << 32));
+        assert_eq!(
+            parse_sexpr("-9223372036854775808").unwrap(),
+            Node::int(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn signed_floats() {
+        assert_eq!(parse_sexpr("-0.5").unwrap(), Node::float(-0.5));
+        assert_eq!(parse_sexpr("-2.75").unwrap(), Node::float(-2.75));
+        assert_eq!(parse_sexpr("+2.5").unwrap(), Node::float(2.5));
+    }
+
+    #[test]
+    fn bare_sign_operators_are_symbols() {
+        assert_eq!(
+            parse_sexpr("(- +)").unwrap(),
+            Node::list([Node::symbol("-"), Node::symbol("+"),]),
+        )
+    }
+
+    #[test]
+    fn double_sign_is_symbol() {
+        assert_eq!(parse_sexpr("--5").unwrap(), Node::symbol("--5"));
+        assert_eq!(parse_sexpr("+-5").unwrap(), Node::symbol("+-5"));
+    }
+
+    #[test]
+    fn float_words_are_symbols() {
+        for word in &["inf", "-inf", "nan", "NaN", "infinity"] {
+            assert_eq!(parse_sexpr(word).unwrap(), Node::symbol(word));
+        }
+    }
+
+    #[test]
+    fn scientific_floats() {
+        assert_eq!(parse_sexpr("1e10").unwrap(), Node::float(1e10));
+        assert_eq!(parse_sexpr("1.5e-3").unwrap(), Node::float(1.5e-3));
+        assert_eq!(parse_sexpr("6.022e23").unwrap(), Node::float(6.022e23));
+        assert_eq!(parse_sexpr("-2E+4").unwrap(), Node::float(-2e4));
+    }
+
+    #[test]
+    fn malformed_exponent_is_symbol() {
+        for word in &["1e", "1e+", "1.5e-", "2e3e4"] {
+            assert_eq!(parse_sexpr(word).unwrap(), Node::symbol(word));
+        }
+    }
+
+    #[cfg(not(feature = "small-float"))]
+    #[test]
+    fn scientific_display_roundtrip() {
+        let node = parse_sexpr("(6.022e23 1e-7 1.5e-3 1e300)").unwrap();
+
+        assert_eq!(node.to_string(), "(6.022e23 1e-7 0.0015 1e300)");
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn non_finite_display_roundtrip() {
+        let node = parse_sexpr("(+inf.0 -inf.0 +nan.0 -nan.0 inf nan)").unwrap();
+
+        assert_eq!(node.to_string(), "(+inf.0 -inf.0 +nan.0 +nan.0 inf nan)");
+        assert!(parse_sexpr(&node.to_string())
+            .unwrap()
+            .structurally_eq(&node));
+        assert_eq!(
+            Node::float(super::Float::NEG_INFINITY).to_string(),
+            "-inf.0"
+        );
+        assert_eq!(parse_sexpr("1e400").unwrap().to_string(), "+inf.0");
+        assert_eq!(Node::symbol("+inf.0").to_string(), "|+inf.0|");
+        assert_eq!(parse_sexpr("|+inf.0|").unwrap(), Node::symbol("+inf.0"));
+    }
+
+    #[cfg(feature = "small-float")]
+    #[test]
+    fn small_floats() {
+        let node = parse_sexpr("(6.022e23 1e-7 1.5e-3 3.14159265358979)").unwrap();
+
+        assert_eq!(node.to_string(), "(6.022e23 1e-7 0.0015 3.1415927)");
+        assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+        assert_eq!(parse_sexpr("0.1").unwrap(), Node::float(0.1_f32));
+        assert_eq!(parse_sexpr("1e39").unwrap(), Node::float(f32::INFINITY));
+        assert!(!Int(16_777_217).numeric_eq(&Float(16_777_216.0)));
+        assert_eq!(std::mem::size_of::<super::Float>(), 4);
+    }
+
+    #[test]
+    fn negative_operand() {
+        assert_eq!(
+            parse_sexpr("(- -5 -0.5)").unwrap(),
+            Node::list([Node::symbol("-"), Node::int(-5), Node::float(-0.5),]),
+        )
+    }
+
+    #[test]
+    fn radix_ints() {
+        assert_eq!(
+            parse_sexpr("(#x1F #o17 #b1010 #XfF)").unwrap(),
+            Node::list([Node::int(31), Node::int(15), Node::int(10), Node::int(255),]),
+        )
+    }
+
+    #[test]
+    fn signed_radix_int() {
+        assert_eq!(parse_sexpr("#x-10").unwrap(), Node::int(-16));
+    }
+
+    #[test]
+    fn radix_int_display_is_decimal() {
+        assert_eq!(parse_sexpr("#x1F").unwrap().to_string(), "31");
+    }
+
+    #[test]
+    fn keep_radix_roundtrip() {
+        let options = ParseOptions::new().keep_radix(true);
+
+        for code in ["#xFF", "#o17", "#b101", "#x-1F", "#b0"] {
+            let node = parse_sexpr_with(code, &options).unwrap();
+
+            assert_eq!(node.to_string(), code);
+            assert_eq!(parse_sexpr_with(&node.to_string(), &options).unwrap(), node);
+        }
+
+        let node = parse_sexpr_with("(#xff #O17 17)", &options).unwrap();
+
+        assert_eq!(
+            node,
+            Node::list([
+                Atom(RadixInt(255, Radix::Hex)),
+                Atom(RadixInt(15, Radix::Octal)),
+                Node::int(17),
+            ])
+        );
+        assert_eq!(node.to_string(), "(#xFF #o17 17)");
+        assert_eq!(
+            parse_sexpr_with("#x-8000000000000000", &options)
+                .unwrap()
+                .to_string(),
+            "#x-8000000000000000"
+        );
+    }
+
+    #[test]
+    fn radix_int_is_an_int() {
+        let hex = RadixInt(255, Radix::Hex);
+
+        assert_eq!(hex.as_int(), Some(255));
+        assert!(hex.numeric_eq(&Int(255)));
+        assert!(Float(255.0).numeric_eq(&hex));
+        assert_ne!(hex, Int(255));
+    }
+
+    #[test]
+    fn invalid_radix_digit_err() {
+        assert_eq!(
+            parse_sexpr("(+ 1 #b123)").unwrap_err(),
+            SexprSyntaxError::InvalidNumber("#b123".to_owned(), pos(5, 1, 6)),
+        );
+        assert_eq!(
+            parse_sexpr("#x").unwrap_err(),
+            SexprSyntaxError::InvalidNumber("#x".to_owned(), pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn rationals() {
+        assert_eq!(
+            parse_sexpr("(3/4 -1/2 +5/3)").unwrap(),
+            Node::list([
+                Node::Atom(Rational { num: 3, den: 4 }),
+                Node::Atom(Rational { num: -1, den: 2 }),
+                Node::Atom(Rational { num: 5, den: 3 }),
+            ]),
+        );
+    }
+
+    #[test]
+    fn rationals_are_reduced() {
+        assert_eq!(
+            parse_sexpr("6/8").unwrap(),
+            Node::Atom(Rational { num: 3, den: 4 })
+        );
+        assert_eq!(
+            parse_sexpr("-10/4").unwrap(),
+            Node::Atom(Rational { num: -5, den: 2 })
+        );
+        assert_eq!(parse_sexpr("4/2").unwrap(), Node::int(2));
+        assert_eq!(parse_sexpr("0/5").unwrap(), Node::int(0));
+        assert_eq!(parse_sexpr("7/1").unwrap(), Node::int(7));
+    }
+
+    #[test]
+    fn rational_display_roundtrip() {
+        for code in ["3/4", "-5/2", "(1/3 2/3)"].iter() {
+            let node = parse_sexpr(code).unwrap();
+
+            assert_eq!(node.to_string(), *code);
+            assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node);
+        }
+    }
+
+    #[test]
+    fn invalid_rational_err() {
+        assert_eq!(
+            parse_sexpr("(x 1/0)").unwrap_err(),
+            SexprSyntaxError::InvalidNumber("1/0".to_owned(), pos(3, 1, 4)),
+        );
+
+        let too_big = format!("1/{}0", i64::MAX);
+
+        assert_eq!(
+            parse_sexpr(&too_big).unwrap_err(),
+            SexprSyntaxError::IntegerOverflow(too_big.clone(), pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn strict_symbols() {
+        let strict = ParseOptions::new().strict_symbols(true);
+
+        assert_eq!(
+            parse_sexpr_with("(<= x-1 y?)", &strict).unwrap(),
+            parse_sexpr("(<= x-1 y?)").unwrap(),
+        );
+        assert_eq!(
+            parse_sexpr_with("(f #foo)", &strict).unwrap_err(),
+            SexprSyntaxError::InvalidSymbol("#foo".to_owned(), pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr_with("a.b", &strict).unwrap_err(),
+            SexprSyntaxError::InvalidSymbol("a.b".to_owned(), pos(0, 1, 1)),
+        );
+        // Only symbols are checked, not the other atoms.
+        assert_eq!(
+            parse_sexpr_with("(#t #\\a #x1F :k-ey \"a.b\")", &strict).unwrap(),
+            parse_sexpr("(#t #\\a #x1F :k-ey \"a.b\")").unwrap(),
+        );
+        assert_eq!(
+            parse_sexpr_with("#foo", &ParseOptions::new()).unwrap(),
+            Node::symbol("#foo"),
+        );
+        assert_eq!(
+            parse_sexpr_with("a$b", &strict).unwrap_err(),
+            SexprSyntaxError::InvalidSymbol("a$b".to_owned(), pos(0, 1, 1)),
+        );
+        assert_eq!(parse_sexpr("a$b").unwrap(), Node::symbol("a$b"));
+        // A `,` is an unquote, which ends the token before it either way.
+        assert_eq!(
+            parse_sexpr_with("a,b", &strict).unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(1, 1, 2)),
+        );
+    }
+
+    #[test]
+    fn custom_symbol_chars() {
+        let options = ParseOptions::new().strict_symbols(true).symbol_chars("-_");
+
+        assert_eq!(
+            parse_sexpr_with("snake_case", &options).unwrap(),
+            Node::symbol("snake_case"),
+        );
+        assert_eq!(parse_sexpr_with("λ", &options).unwrap(), Node::symbol("λ"));
+        assert_eq!(
+            parse_sexpr_with("(+ 1 2)", &options).unwrap_err(),
+            SexprSyntaxError::InvalidSymbol("+".to_owned(), pos(1, 1, 2)),
+        );
+    }
+
+    #[test]
+    fn datum_comments() {
+        assert_eq!(
+            parse_sexpr("(+ 1 #;2 3)").unwrap(),
+            parse_sexpr("(+ 1 3)").unwrap()
+        );
+        assert_eq!(
+            parse_sexpr("(a #; (b (c d) 'e) f)").unwrap(),
+            parse_sexpr("(a f)").unwrap()
+        );
+        assert_eq!(
+            parse_sexpr("(a #;#;b c d)").unwrap(),
+            parse_sexpr("(a d)").unwrap()
+        );
+        assert_eq!(parse_sexpr("'#;a b").unwrap(), parse_sexpr("'b").unwrap());
+        assert_eq!(
+            parse_sexpr("(a . #;b c)").unwrap(),
+            parse_sexpr("(a . c)").unwrap()
+        );
+        assert_eq!(parse_sexpr("#;a b #;c").unwrap(), Node::symbol("b"));
+        assert_eq!(parse_program("#;(a) b #;c").unwrap(), [Node::symbol("b")]);
+        assert_eq!(parse_sexpr("#;a").unwrap_err(), SexprSyntaxError::Empty);
+        assert!(parse_program("#;(a b)").unwrap().is_empty());
+    }
+
+    #[test]
+    fn datum_comment_errs() {
+        assert_eq!(
+            parse_sexpr("(a #;)").unwrap_err(),
+            SexprSyntaxError::DanglingReaderMacro(pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr("a #;").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(2, 1, 3)),
+        );
+        assert_eq!(
+            parse_program("#;").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("a #;b c").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(6, 1, 7)),
+        );
+        assert_eq!(
+            parse_sexpr("a #;b )").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(6, 1, 7)),
+        );
+        assert!(!is_complete("(a #;"));
+        assert!(!is_complete("#;(a"));
+    }
+
+    #[test]
+    fn reader_macros_disabled() {
+        let options = ParseOptions::new().allow_reader_macros(false);
+
+        assert_eq!(
+            parse_sexpr_with("(a 'b)", &options).unwrap_err(),
+            SexprSyntaxError::DisabledReaderMacro(pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr_with("(a ,@b)", &options).unwrap_err(),
+            SexprSyntaxError::DisabledReaderMacro(pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr_with("(quote \"'\")", &options).unwrap(),
+            parse_sexpr("(quote \"'\")").unwrap(),
+        );
+    }
+
+    #[test]
+    fn combined_options() {
+        let options = ParseOptions::new()
+            .max_depth(2)
+            .allow_reader_macros(false)
+            .strict_symbols(true);
+
+        assert_eq!(
+            parse_sexpr_with("(quote (x))", &options).unwrap(),
+            parse_sexpr("'(x)").unwrap(),
+        );
+        assert_eq!(
+            parse_sexpr_with("(a (b (c)))", &options).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(6, 1, 7)),
+        );
+        assert_eq!(
+            parse_sexpr_with("(a #q)", &options).unwrap_err(),
+            SexprSyntaxError::InvalidSymbol("#q".to_owned(), pos(3, 1, 4)),
+        );
+    }
+
+    #[test]
+    fn pipe_quoted_symbols() {
+        assert_eq!(
+            parse_sexpr("(|hello world| |(| |12| ||)").unwrap(),
+            Node::list([
+                Node::symbol("hello world"),
+                Node::symbol("("),
+                Node::symbol("12"),
+                Node::symbol(""),
+            ]),
+        );
+        assert_eq!(
+            parse_sexpr("|a\\|b\\\\c\\n|").unwrap(),
+            Node::symbol("a|b\\c\n")
+        );
+        assert_eq!(parse_sexpr("|x|").unwrap(), parse_sexpr("x").unwrap());
+        assert_eq!(
+            parse_sexpr_with("|#foo|", &ParseOptions::new().strict_symbols(true)).unwrap(),
+            Node::symbol("#foo"),
+        );
+    }
+
+    #[test]
+    fn pipe_quoted_symbol_errs() {
+        assert_eq!(
+            parse_sexpr("(a |b c)").unwrap_err(),
+            SexprSyntaxError::UnterminatedSymbol(pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr("|a\\\"|").unwrap_err(),
+            SexprSyntaxError::InvalidEscape('"', pos(2, 1, 3)),
+        );
+        assert!(!is_complete("(a |b"));
+    }
+
+    #[test]
+    fn punctuated_symbols() {
+        let strict = ParseOptions::new().strict_symbols(true);
+
+        for name in [
+            "list->vector",
+            "set!",
+            "string=?",
+            "1+",
+            "1-",
+            "->",
+            "<=",
+            "-x",
+        ] {
+            assert_eq!(parse_sexpr(name).unwrap(), Node::symbol(name), "{}", name);
+            assert_eq!(
+                parse_sexpr_with(name, &strict).unwrap(),
+                Node::symbol(name),
+                "{}",
+                name
+            );
+            assert_eq!(Node::symbol(name).to_string(), name);
+        }
+
+        assert_eq!(
+            parse_sexpr("(1+ -1 +1)").unwrap(),
+            Node::list([Node::symbol("1+"), Node::int(-1), Node::int(1)])
+        );
+    }
+
+    #[test]
+    fn symbol_display_roundtrip() {
+        let names = [
+            "x",
+            "list->vector",
+            "λ",
+            "+",
+            "...",
+            "a|b",
+            "hello world",
+            "(",
+            "a;b",
+            "",
+            "|",
+            "|x",
+            "x\"y",
+            "new\nline",
+            "12",
+            "+1",
+            "3/4",
+            "#t",
+            "#\\a",
+            ":k",
+            ".",
+            "#| x",
+        ];
+
+        for name in names.iter() {
+            let node = Node::symbol(name);
+
+            assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node, "{}", node);
+        }
+
+        assert_eq!(Node::symbol("list->vector").to_string(), "list->vector");
+        assert_eq!(Node::symbol("+").to_string(), "+");
+        assert_eq!(Node::symbol("hello world").to_string(), "|hello world|");
+        assert_eq!(Node::symbol("12").to_string(), "|12|");
+        assert_eq!(Node::symbol("|x").to_string(), "|\\|x|");
+    }
+
+    #[test]
+    fn distinct_nil() {
+        let options = ParseOptions::new().distinct_nil(true);
+        let node = parse_sexpr_with("(nil () |nil| nil?)", &options).unwrap();
+
+        assert_eq!(
+            node,
+            Node::list([
+                Atom(Nil),
+                Node::list([]),
+                Node::symbol("nil"),
+                Node::symbol("nil?"),
+            ]),
+        );
+        assert_eq!(node.to_string(), "(nil () nil nil?)");
+        assert_eq!(
+            parse_sexpr("(nil ())").unwrap(),
+            Node::list([Node::symbol("nil"), Node::list([])]),
+        );
+    }
+
+    #[test]
+    fn fold_case() {
+        let code = "(Define CAR :Key |MiXed| \"Str\" #\\A #T #FALSE)";
+
+        assert_eq!(
+            parse_sexpr(code).unwrap().to_string(),
+            "(Define CAR :Key MiXed \"Str\" #\\A #T #FALSE)"
+        );
+
+        let options = ParseOptions::new().fold_case(true);
+        let node = parse_sexpr_with(code, &options).unwrap();
+
+        assert_eq!(
+            node.to_string(),
+            "(define car :key MiXed \"Str\" #\\A #t #f)"
+        );
+        assert_eq!(parse_sexpr_with("#T", &options).unwrap(), Atom(Bool(true)));
+        assert_eq!(parse_sexpr_with("#F", &options).unwrap(), Atom(Bool(false)));
+        assert_eq!(
+            parse_sexpr_with("#\\Tab", &options).unwrap(),
+            Atom(Char('\t'))
+        );
+        assert_eq!(
+            parse_sexpr_with("CAR", &options).unwrap(),
+            parse_sexpr_with("car", &options).unwrap()
+        );
+        assert_ne!(parse_sexpr("CAR").unwrap(), parse_sexpr("car").unwrap());
+        assert_eq!(
+            parse_sexpr_with("NIL", &options.distinct_nil(true)).unwrap(),
+            Atom(Nil)
+        );
+    }
+
+    #[test]
+    fn vectors() {
+        assert_eq!(
+            parse_sexpr("#(1 (a #()) #(b))").unwrap(),
+            Node::Vector(vec![
+                Node::int(1),
+                Node::list([Node::symbol("a"), Node::Vector(vec![])]),
+                Node::Vector(vec![Node::symbol("b")]),
+            ]),
+        );
+        assert_eq!(
+            parse_sexpr("'#(x)").unwrap(),
+            Node::list([Node::symbol("quote"), Node::Vector(vec![Node::symbol("x")])]),
+        );
+        assert_ne!(
+            parse_sexpr("#(1 2)").unwrap(),
+            parse_sexpr("(1 2)").unwrap()
+        );
+    }
+
+    #[test]
+    fn vector_display_roundtrip() {
+        for code in ["#(1 2 3)", "#()", "(a #(b (c . d)) #(#(e)))"].iter() {
+            assert_eq!(parse_sexpr(code).unwrap().to_string(), *code);
+        }
+    }
+
+    #[test]
+    fn vector_errs() {
+        assert_eq!(
+            parse_sexpr("(a #(1 2)").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("#(1 (2)").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("#(1 . 2)").unwrap_err(),
+            SexprSyntaxError::InvalidDottedPair(pos(4, 1, 5)),
+        );
+        assert_eq!(
+            parse_sexpr_with("#(1 #(2))", &ParseOptions::new().max_depth(1)).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(4, 1, 5)),
+        );
+    }
+
+    #[test]
+    fn bracket_lists() {
+        assert_eq!(
+            parse_sexpr("(let [(x 1) [y 2]] [x . y])").unwrap(),
+            parse_sexpr("(let ((x 1) (y 2)) (x . y))").unwrap(),
+        );
+        assert_eq!(
+            parse_sexpr("a[0]").unwrap_err(),
+            SexprSyntaxError::TrailingTokens(pos(1, 1, 2)),
+        );
+        assert_eq!(Node::symbol("a[0]").to_string(), "|a[0]|");
+    }
+
+    #[test]
+    fn bracket_vectors() {
+        let options = ParseOptions::new().bracket_vectors(true);
+
+        assert_eq!(
+            parse_sexpr_with("(f [1 [2]] '[])", &options).unwrap(),
+            parse_sexpr("(f #(1 #(2)) '#())").unwrap(),
+        );
+        assert_eq!(
+            parse_sexpr_with("[1 . 2]", &options).unwrap_err(),
+            SexprSyntaxError::InvalidDottedPair(pos(3, 1, 4)),
+        );
+    }
+
+    #[test]
+    fn mismatched_bracket_err() {
+        assert_eq!(
+            parse_sexpr("(1 2]").unwrap_err(),
+            SexprSyntaxError::MismatchedBracket {
+                open: pos(0, 1, 1),
+                close: pos(4, 1, 5),
+            },
+        );
+        assert_eq!(
+            parse_sexpr("[1 (2])").unwrap_err(),
+            SexprSyntaxError::MismatchedBracket {
+                open: pos(3, 1, 4),
+                close: pos(5, 1, 6),
+            },
+        );
+        assert_eq!(
+            parse_sexpr("#(1 2]").unwrap_err(),
+            SexprSyntaxError::MismatchedBracket {
+                open: pos(0, 1, 1),
+                close: pos(5, 1, 6),
+            },
+        );
+        assert_eq!(
+            parse_sexpr("[1 2)").unwrap_err(),
+            SexprSyntaxError::MismatchedBracket {
+                open: pos(0, 1, 1),
+                close: pos(4, 1, 5),
+            },
+        );
+        assert_eq!(
+            parse_sexpr("[1 2]]").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(5, 1, 6)),
+        );
+        assert_eq!(
+            parse_sexpr("(a\n  [b c)").unwrap_err().to_string(),
+            "Closing bracket at line 2, column 7 does not match the opening one at line 2, column 3",
+        );
+        assert_eq!(
+            parse_sexpr("[1 2").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+    }
+
+    #[test]
+    fn slashes_in_symbols() {
+        for code in ["/", "a/b", "1/", "/2", "1/2/3", "1.5/2", "1/-2", "and/or"].iter() {
+            assert!(
+                matches!(parse_sexpr(code).unwrap(), Node::Atom(Symbol(_))),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn atom_bigint() {
+        use num_bigint::BigInt as Big;
+
+        let literal = "99999999999999999999";
+
+        assert_eq!(
+            parse_sexpr(literal).unwrap(),
+            Atom(BigInt(literal.parse::<Big>().unwrap())),
+        );
+        assert_eq!(
+            parse_sexpr("-99999999999999999999").unwrap(),
+            Atom(BigInt(-literal.parse::<Big>().unwrap())),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn integer_overflow_err() {
+        assert_eq!(
+            parse_sexpr("(x 99999999999999999999)").unwrap_err(),
+            SexprSyntaxError::IntegerOverflow("99999999999999999999".to_owned(), pos(3, 1, 4)),
+        );
+        assert_eq!(
+            parse_sexpr("-9223372036854775809").unwrap_err(),
+            SexprSyntaxError::IntegerOverflow("-9223372036854775809".to_owned(), pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("#x10000000000000000").unwrap_err(),
+            SexprSyntaxError::IntegerOverflow("#x10000000000000000".to_owned(), pos(0, 1, 1)),
+        );
+        // Written as a float, the same value is fine.
+        assert_eq!(
+            parse_sexpr("99999999999999999999.0").unwrap(),
+            Node::float(1e20)
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_only_on_overflow() {
+        assert_eq!(
+            parse_sexpr(&i64::MAX.to_string()).unwrap(),
+            Node::int(i64::MAX),
+        );
+        assert_eq!(
+            parse_sexpr(&i64::MIN.to_string()).unwrap(),
+            Node::int(i64::MIN),
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn radix_bigint() {
+        use num_bigint::BigInt as Big;
+
+        assert_eq!(
+            parse_sexpr("#x100000000000000000000").unwrap(),
+            Atom(BigInt(Big::from(1) << 80)),
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_display() {
+        let literal = "(123456789012345678901234567890 -98765432109876543210)";
+
+        assert_eq!(parse_sexpr(literal).unwrap().to_string(), literal);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_json_roundtrip() {
+        let node = parse_sexpr("(car (list 1 (+ 2 3) (* (+ 4 5) 6)))").unwrap();
+        let json = serde_json::to_string(&node).unwrap();
+
+        assert!(json.starts_with(r#"{"List":[{"Atom":{"Symbol":"car"}},"#));
+        assert_eq!(serde_json::from_str::<Node>(&json).unwrap(), node);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_distinguishes_atom_kinds() {
+        let node = parse_sexpr("(a \"a\" :a #\\a 1 1.0 #t (b . c))").unwrap();
+        let json = serde_json::to_value(&node).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({"List": [
+                {"Atom": {"Symbol": "a"}},
+                {"Atom": {"Str": "a"}},
+                {"Atom": {"Keyword": "a"}},
+                {"Atom": {"Char": "a"}},
+                {"Atom": {"Int": 1}},
+                {"Atom": {"Float": 1.0}},
+                {"Atom": {"Bool": true}},
+                {"Pair": [{"Atom": {"Symbol": "b"}}, {"Atom": {"Symbol": "c"}}]},
+            ]}),
+        );
+        assert_eq!(serde_json::from_value::<Node>(json).unwrap(), node);
+    }
+
+    #[test]
+    fn error_position_multiline() {
+        assert_eq!(
+            parse_sexpr("(a\n  b))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(7, 2, 5)),
+        )
+    }
+
+    #[test]
+    fn error_position_counts_chars() {
+        assert_eq!(
+            parse_sexpr("(\"é\" 1))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(8, 1, 8)),
+        )
+    }
+
+    #[test]
+    fn error_position_display() {
+        assert_eq!(
+            parse_sexpr("(a\n  b))").unwrap_err().to_string(),
+            "Unexpected closing paren found at line 2, column 5",
+        )
+    }
+
+    #[test]
+    fn token_kinds_and_spans() {
+        let token = |kind, span| Token { kind, span };
+
+        assert_eq!(
+            tokenize("(a . \"(\") ,@'b #(|c|)")
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![
+                token(TokenKind::LParen, 0..1),
+                token(TokenKind::Atom("a"), 1..2),
+                token(TokenKind::Dot, 3..4),
+                token(TokenKind::Str("(".into()), 5..8),
+                token(TokenKind::RParen, 8..9),
+                token(TokenKind::UnquoteSplicing, 10..12),
+                token(TokenKind::Quote, 12..13),
+                token(TokenKind::Atom("b"), 13..14),
+                token(TokenKind::LVector, 15..17),
+                token(TokenKind::Symbol("c".into()), 17..20),
+                token(TokenKind::RParen, 20..21),
+            ],
+        )
+    }
+
+    #[test]
+    fn token_spans_skip_comments() {
+        let spans: Vec<_> = tokenize("; hi\n#| there |# foo")
+            .map(|token| token.unwrap().span)
+            .collect();
+
+        assert_eq!(spans, vec![17..20]);
+    }
+
+    #[test]
+    fn tokens_keeping_comments() {
+        let tokens: Vec<_> = tokenize("; hi\n#| there |# foo")
+            .keep_comments()
+            .map(|token| token.unwrap())
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    kind: TokenKind::Comment("; hi"),
+                    span: 0..4,
+                },
+                Token {
+                    kind: TokenKind::Comment("#| there |#"),
+                    span: 5..16,
+                },
+                Token {
+                    kind: TokenKind::Atom("foo"),
+                    span: 17..20,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn program_multiple_forms() {
+        assert_eq!(
+            parse_program("(define x 1)\n(define y 2)").unwrap(),
+            vec![
+                Node::list([Node::symbol("define"), Node::symbol("x"), Node::int(1),]),
+                Node::list([Node::symbol("define"), Node::symbol("y"), Node::int(2),]),
+            ],
+        )
+    }
+
+    #[test]
+    fn program_atoms_and_quotes() {
+        assert_eq!(
+            parse_program("1 'a \"b\"").unwrap(),
+            vec![
+                Node::int(1),
+                Node::list([Node::symbol("quote"), Node::symbol("a"),]),
+                Node::string("b"),
+            ],
+        )
+    }
+
+    #[test]
+    fn program_trailing_comments() {
+        assert_eq!(
+            parse_program("(a)\n; the end\n#| really |#\n  ").unwrap(),
+            vec![Node::list([Node::symbol("a")])],
+        )
+    }
+
+    #[test]
+    fn program_empty() {
+        assert_eq!(parse_program("  ; nothing\n").unwrap(), Vec::<Node>::new());
+    }
+
+    #[test]
+    fn program_unmatched_paren_err() {
+        assert_eq!(
+            parse_program("(a)\n(b").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(4, 2, 1)),
+        );
+        assert_eq!(
+            parse_program("(a))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(3, 1, 4)),
+        );
+    }
+
+    #[test]
+    fn unmatched_paren_after_nested_lists_err() {
+        assert_eq!(
+            parse_sexpr("(a (b) c (d)").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)),
+        );
+        assert_eq!(
+            parse_sexpr("(a (b) c (d)))").unwrap_err(),
+            SexprSyntaxError::UnexpectedCloseParen(pos(13, 1, 14)),
+        );
+    }
+
+    #[test]
+    fn last_element_is_list() {
+        assert_eq!(
+            parse_sexpr("(a (b) c (d))").unwrap(),
+            Node::list([
+                Node::symbol("a"),
+                Node::list([Node::symbol("b")]),
+                Node::symbol("c"),
+                Node::list([Node::symbol("d")]),
+            ]),
+        );
+        assert_eq!(
+            parse_sexpr("((()))").unwrap(),
+            Node::list([Node::list([Node::list([])])]),
+        );
+    }
+
+    #[test]
+    fn depth_limit_exceeded_err() {
+        let depth = 10_000;
+        let sexpr = "(".repeat(depth) + &")".repeat(depth);
+
+        assert_eq!(
+            parse_sexpr(&sexpr).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(512, 1, 513)),
+        );
+    }
+
+    #[test]
+    fn depth_limit_quotes_err() {
+        let sexpr = "'".repeat(100_000) + "x";
+
+        assert_eq!(
+            parse_sexpr(&sexpr).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(512, 1, 513)),
+        );
+    }
+
+    #[test]
+    fn custom_depth_limit() {
+        assert_eq!(
+            parse_sexpr_with_max_depth("((('a)))", 4).unwrap(),
+            Node::list([Node::list([Node::list([Node::list([
+                Node::symbol("quote"),
+                Node::symbol("a"),
+            ])])])]),
+        );
+        assert_eq!(
+            parse_sexpr_with_max_depth("(a (b (c)))", 2).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(6, 1, 7)),
+        );
+        assert_eq!(
+            parse_sexpr_with_max_depth("atom", 0).unwrap(),
+            Node::symbol("atom")
+        );
+    }
+
+    #[test]
+    fn deep_nesting_within_limit() {
+        let depth = 2_000;
+        let sexpr = "(".repeat(depth) + "x" + &")".repeat(depth);
+
+        assert!(parse_sexpr_with_max_depth(&sexpr, depth).is_ok());
+        assert_eq!(
+            parse_sexpr_with_max_depth(&sexpr, depth - 1).unwrap_err(),
+            SexprSyntaxError::DepthLimitExceeded(pos(depth - 1, 1, depth)),
+        );
+    }
+
+    #[test]
+    fn tokenizer_is_lazy() {
+        let mut tokens = tokenize("(a) \"never closed");
+
+        assert_eq!(tokens.next().unwrap().unwrap().kind, TokenKind::LParen);
+        assert_eq!(tokens.next().unwrap().unwrap().kind, TokenKind::Atom("a"));
+        assert_eq!(tokens.next().unwrap().unwrap().kind, TokenKind::RParen);
+        assert_eq!(
+            tokens.next().unwrap().unwrap_err(),
+            SexprSyntaxError::UnterminatedString(pos(4, 1, 5)),
+        );
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn tokenizer_stops_after_error() {
+        let mut tokens = tokenize(r#""bad \q" after"#);
+
+        assert_eq!(
+            tokens.next().unwrap().unwrap_err(),
+            SexprSyntaxError::InvalidEscape('q', pos(5, 1, 6)),
+        );
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn parser_reads_one_form_at_a_time() {
+        let mut forms = Parser::new("(a) (b) (c", ParseOptions::new());
 
-    if tokens.last().unwrap() != ")" {
-        return Err(SexprSyntaxError::UnmatchedParen);
+        assert_eq!(
+            forms.next().unwrap().unwrap(),
+            Node::list([Node::symbol("a")]),
+        );
+        assert_eq!(
+            forms.next().unwrap().unwrap(),
+            Node::list([Node::symbol("b")]),
+        );
+        assert_eq!(
+            forms.next().unwrap().unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(8, 1, 9)),
+        );
     }
 
-    let inner_sexpr_tokens = &tokens[1..tokens.len() - 1];
+    #[test]
+    fn token_spans_map_to_source() {
+        let code = "(greet \"wor\\\"ld\")";
+        let text: Vec<_> = tokenize(code)
+            .map(|token| &code[token.unwrap().span])
+            .collect();
 
-    let mut parsed_list = vec![];
-    let mut element_start = 0;
+        assert_eq!(text, vec!["(", "greet", "\"wor\\\"ld\"", ")"]);
+    }
 
-    while element_start < inner_sexpr_tokens.len() {
-        let token = &inner_sexpr_tokens[element_start];
+    #[test]
+    fn symbol_and_float_display() {
+        assert_eq!(
+            parse_sexpr("(hello 1.5)").unwrap().to_string(),
+            "(hello 1.5)"
+        );
+        assert_eq!(Node::float(2.0).to_string(), "2.0");
+        assert_eq!(Node::float(-0.25).to_string(), "-0.25");
+    }
 
-        let element_end = element_start
-            + if token == "(" {
-                find_matching_paren(&inner_sexpr_tokens[element_start..])
-                    .ok_or(SexprSyntaxError::UnmatchedParen)?
-            } else {
-                0
-            };
+    #[test]
+    fn integer_valued_floats_stay_floats() {
+        let cases = [(2.0, "2.0"), (0.0, "0.0"), (1e10, "10000000000.0")];
 
-        parsed_list.push(parse_tokens(
-            &inner_sexpr_tokens[element_start..=element_end],
-        )?);
+        for (float, displayed) in cases.iter() {
+            let node = Node::float(*float);
 
-        element_start = element_end + 1;
+            assert_eq!(node.to_string(), *displayed);
+            assert_eq!(parse_sexpr(displayed).unwrap(), node);
+        }
     }
 
-    Ok(Node::List(parsed_list))
-}
-
-fn find_matching_paren(tokens: &[String]) -> Option<usize> {
-    let mut num_parens = 0;
+    #[test]
+    fn display_roundtrip() {
+        let samples = [
+            "(car (list 1 (+ 2 3) (* (+ 4 5) 6)))",
+            "(define (area r) (* 3.14159 r r))",
+            "(print \"hello \\\"world\\\"\\n\" 'sym)",
+            "(if #t -1.0 +2)",
+            "`(a ,b ,@(c . d))",
+            "((1 . 2) (3 4 . 5) ())",
+            "(#x1F 1e30 0.1 100.0 .5)",
+        ];
 
-    for (idx, token) in tokens.iter().enumerate() {
-        if token == "(" {
-            num_parens += 1;
-        } else if token == ")" {
-            if num_parens == 1 {
-                return Some(idx);
-            }
+        for sample in samples.iter() {
+            let node = parse_sexpr(sample).unwrap();
 
-            num_parens -= 1;
+            assert_eq!(parse_sexpr(&node.to_string()).unwrap(), node, "{}", sample);
         }
     }
 
-    None
-}
-
-fn parse_atom(atom: &str) -> Atom {
-    if let Ok(integer) = atom.parse() {
-        Atom::Int(integer)
-    } else if let Ok(float) = atom.parse() {
-        Atom::Float(float)
-    } else {
-        Atom::Symbol(atom.to_owned())
+    #[test]
+    fn from_conversions() {
+        assert_eq!(Node::from(5), Node::int(5));
+        assert_eq!(Node::from(1.5), Node::float(1.5));
+        assert_eq!(Node::from("x"), Node::symbol("x"));
+        assert_eq!(Node::from("x".to_owned()), Node::symbol("x"));
+        assert_eq!(Node::from(true), Node::bool(true));
+        assert_eq!(Node::from('c'), Atom(Char('c')));
+        assert_eq!(Node::from(Int(5)), Node::int(5));
     }
-}
 
-fn tokenize(code: &str) -> Vec<String> {
-    code.trim()
-        .replace("(", " ( ")
-        .replace(")", " ) ")
-        .split_whitespace()
-        .map(|token| token.to_owned())
-        .collect()
-}
+    #[test]
+    fn collect_into_list() {
+        let node: Node = vec![Node::from("+"), 1.into(), 2.into()]
+            .into_iter()
+            .collect();
 
-#[derive(Debug, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum SexprSyntaxError {
-    Empty,
-    UnmatchedParen,
-}
+        assert_eq!(node, parse_sexpr("(+ 1 2)").unwrap());
+        assert_eq!(
+            (1..=3).map(Node::from).collect::<Node>(),
+            Node::list([Node::int(1), Node::int(2), Node::int(3)]),
+        );
+    }
 
-impl Error for SexprSyntaxError {}
+    #[test]
+    fn children_of_basic() {
+        let node = parse_sexpr("(+ 1 2)").unwrap();
+        let elements = [Node::symbol("+"), Node::int(1), Node::int(2)];
 
-impl fmt::Display for SexprSyntaxError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Empty => write!(f, "Empty S-expression provided"),
-            Self::UnmatchedParen => write!(f, "Unmatched parentheses found"),
-        }
+        assert!(node.children().eq(elements.iter()));
+        assert_eq!(node.as_list(), Some(&elements[..]));
     }
-}
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Node {
-    List(Vec<Node>),
-    Atom(Atom),
-}
+    #[test]
+    fn children_of_deeply_nested() {
+        let node = parse_sexpr("(car (list 1 (+ 2 3) (* (+ 4 5) 6)))").unwrap();
+        let inner = node.children().nth(1).unwrap();
 
-impl fmt::Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::List(v) => write!(
-                f,
-                "({})",
-                v.iter()
-                    .map(|n| n.to_string())
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            ),
-            Self::Atom(a) => write!(f, "{}", a),
-        }
+        assert_eq!(node.children().count(), 2);
+        assert_eq!(
+            inner
+                .children()
+                .map(|child| child.to_string())
+                .collect::<Vec<_>>(),
+            vec!["list", "1", "(+ 2 3)", "(* (+ 4 5) 6)"],
+        );
+        assert_eq!(inner.as_list().map(|elements| elements.len()), Some(4));
     }
-}
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Atom {
-    Int(isize),
-    Float(f64),
-    Symbol(String),
-}
+    #[test]
+    fn recover_from_errors() {
+        let (forms, errors) =
+            parse_program_recover("(a 1)\n(b 1/0 (c))\n)\n(d [e)) f\n'(g . . h) (i)");
 
-impl fmt::Display for Atom {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Int(i) => write!(f, "{}", i),
-            Self::Float(fl) => write!(f, "f{}", fl),
-            Self::Symbol(s) => write!(f, "\"{}\"", s),
-        }
+        assert_eq!(
+            forms,
+            vec![
+                parse_sexpr("(a 1)").unwrap(),
+                parse_sexpr("f").unwrap(),
+                parse_sexpr("(i)").unwrap(),
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                SexprSyntaxError::InvalidNumber("1/0".to_owned(), pos(9, 2, 4)),
+                SexprSyntaxError::UnexpectedCloseParen(pos(18, 3, 1)),
+                SexprSyntaxError::MismatchedBracket {
+                    open: pos(23, 4, 4),
+                    close: pos(25, 4, 6),
+                },
+                SexprSyntaxError::InvalidDottedPair(pos(34, 5, 5)),
+            ]
+        );
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{parse_sexpr, SexprSyntaxError, Atom::*, Node::*};
 
     #[test]
-    fn empty_err() {
-        assert_eq!(parse_sexpr("").unwrap_err(), SexprSyntaxError::Empty);
+    fn recover_stops_at_lexing_errors() {
+        assert_eq!(
+            parse_program_recover("(a) (b \"c) (d)"),
+            (
+                vec![parse_sexpr("(a)").unwrap()],
+                vec![SexprSyntaxError::UnterminatedString(pos(7, 1, 8))]
+            )
+        );
+        assert_eq!(
+            parse_program_recover("(a (b"),
+            (vec![], vec![SexprSyntaxError::UnexpectedEof(pos(0, 1, 1))])
+        );
+        assert_eq!(parse_program_recover(""), (vec![], vec![]));
     }
 
     #[test]
-    fn atom_int() {
-        assert_eq!(parse_sexpr("1").unwrap(), Atom(Int(1)));
+    fn multi_form_parsing_with_options() {
+        let options = ParseOptions::new().fold_case(true).max_depth(1);
+
+        assert_eq!(
+            parse_program_with("A (B)", &options).unwrap(),
+            vec![Node::symbol("a"), Node::list([Node::symbol("b")])]
+        );
+        assert_eq!(
+            parse_one_with("FOO bar", &options).unwrap(),
+            (Node::symbol("foo"), " bar")
+        );
+        assert_eq!(
+            parse_sexpr_spanned_with("X", &options).unwrap().0,
+            Node::symbol("x")
+        );
+        assert_eq!(
+            parse_program_spanned_with("X Y", &options).unwrap()[1].0,
+            Node::symbol("y")
+        );
+        assert_eq!(
+            parse_program_recover_with("((x)) Y", &options),
+            (
+                vec![Node::symbol("y")],
+                vec![SexprSyntaxError::DepthLimitExceeded(pos(1, 1, 2))]
+            )
+        );
+        assert!(!is_complete("((a"));
+        assert!(is_complete_with("((a", &options));
     }
 
     #[test]
-    fn atom_float() {
-        assert_eq!(parse_sexpr("1.5").unwrap(), Atom(Float(1.5)));
+    fn leading_byte_order_mark() {
+        assert_eq!(
+            parse_sexpr("\u{FEFF}(+ 1 2)").unwrap(),
+            parse_sexpr("(+ 1 2)").unwrap()
+        );
+        assert_eq!(
+            parse_program("\u{FEFF}").unwrap(),
+            parse_program("").unwrap()
+        );
+        assert_eq!(
+            parse_sexpr("\u{FEFF}(+ 1"),
+            Err(SexprSyntaxError::UnexpectedEof(pos(3, 1, 1)))
+        );
+        assert_eq!(
+            parse_sexpr("\u{FEFF}\u{FEFF}x").unwrap(),
+            Node::symbol("\u{FEFF}x")
+        );
+        assert_eq!(tokenize("\u{FEFF}a").next().unwrap().unwrap().span, 3..4);
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn atom_str() {
+    fn from_reader() {
+        use super::parse_from_reader;
+        use std::io::{self, Cursor};
+
         assert_eq!(
-            parse_sexpr("hello").unwrap(),
-            Atom(Symbol("hello".to_owned())),
-        )
+            parse_from_reader(Cursor::new(b"(+ 1 2)")).unwrap(),
+            parse_sexpr("(+ 1 2)").unwrap()
+        );
+
+        let err = parse_from_reader(Cursor::new(b"(+ 1")).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<SexprSyntaxError>(),
+            Some(&SexprSyntaxError::UnexpectedEof(pos(0, 1, 1)))
+        );
+
+        let err = parse_from_reader(Cursor::new(b"(\xff)")).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::InvalidData)
+        );
     }
 
     #[test]
-    fn empty_list() {
-        assert_eq!(parse_sexpr("()").unwrap(), List(vec![]),)
+    fn match_list_by_head() {
+        let node = parse_sexpr("(if a b c)").unwrap();
+        let rest = [Node::symbol("a"), Node::symbol("b"), Node::symbol("c")];
+
+        assert_eq!(node.match_list("if"), Some(&rest[..]));
+        assert_eq!(node.match_form("if", 3), Some(&rest[..]));
+        assert_eq!(node.match_form("if", 2), None);
+        assert_eq!(parse_sexpr("(while a b)").unwrap().match_list("if"), None);
+        assert_eq!(parse_sexpr("(if)").unwrap().match_list("if"), Some(&[][..]));
+        assert_eq!(parse_sexpr("(\"if\" a)").unwrap().match_list("if"), None);
+        assert_eq!(Node::symbol("if").match_list("if"), None);
     }
 
     #[test]
-    fn basic() {
+    fn long_dotted_list_on_a_small_stack() {
+        use std::{collections::hash_map::DefaultHasher, hash::Hash, thread};
+
+        let code = format!("({}. y)", "x ".repeat(100_000));
+
+        thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                let node = parse_sexpr(&code).unwrap();
+                let copy = node.clone();
+
+                assert_eq!(node, copy);
+                assert!(node.structurally_eq(&copy));
+                assert_eq!(node.children().count(), 100_001);
+                assert_eq!(node.map_atoms(|atom| atom.clone()), node);
+                assert_eq!(node.to_string(), code);
+
+                let mut hasher = DefaultHasher::new();
+                node.hash(&mut hasher);
+                drop(node);
+                drop(copy);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn children_of_pairs_and_atoms() {
+        let node = parse_sexpr("(a b . c)").unwrap();
+
         assert_eq!(
-            parse_sexpr("(+ 1 2)").unwrap(),
-            List(vec![
-                Atom(Symbol("+".to_owned())),
-                Atom(Int(1)),
-                Atom(Int(2))
-            ]),
+            node.children().collect::<Vec<_>>(),
+            vec![&Node::symbol("a"), &Node::symbol("b"), &Node::symbol("c")],
         );
+        assert_eq!(node.as_list(), None);
+        assert_eq!(Node::int(1).children().count(), 0);
+        assert_eq!(Node::int(1).as_list(), None);
     }
 
     #[test]
-    fn single_unmatched_paren_err() {
-        assert_eq!(
-            parse_sexpr("(").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
-        )
+    fn structurally_eq_nan() {
+        let a = Node::list([Node::symbol("f"), Node::float(super::Float::NAN)]);
+        let b = Node::list([Node::symbol("f"), Node::float(super::Float::NAN)]);
+
+        assert_ne!(a, b);
+        assert!(a.structurally_eq(&b));
+        assert!(!a.structurally_eq(&Node::list([Node::symbol("f"), Node::float(1.0)])));
     }
 
     #[test]
-    fn single_unmatched_back_paren_err() {
-        assert_eq!(
-            parse_sexpr(")").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
-        )
+    fn structurally_eq_nested() {
+        let a = parse_sexpr("(a (b . 1.5) (c))").unwrap();
+
+        assert!(a.structurally_eq(&a.clone()));
+        assert!(!a.structurally_eq(&parse_sexpr("(a (b . 1.5) (c d))").unwrap()));
+        assert!(!a.structurally_eq(&parse_sexpr("(a (b 1.5) (c))").unwrap()));
+        assert!(!Node::float(0.0).structurally_eq(&Node::float(-0.0)));
     }
 
     #[test]
-    fn unmatched_paren_err() {
+    fn numeric_eq() {
+        assert_ne!(Int(2), Float(2.0));
+        assert!(Int(2).numeric_eq(&Float(2.0)));
+        assert!(Float(2.0).numeric_eq(&Int(2)));
+        assert_ne!(Node::int(2), Node::float(2.0));
+
+        let numeric_eq = |a: &str, b: &str| {
+            let (a, b) = (parse_sexpr(a).unwrap(), parse_sexpr(b).unwrap());
+            let (a, b) = (a.as_atom().unwrap(), b.as_atom().unwrap());
+
+            a.numeric_eq(b) && b.numeric_eq(a)
+        };
+
+        assert!(numeric_eq("-0.0", "0"));
+        assert!(numeric_eq("1/2", "0.5"));
+        assert!(numeric_eq("6/4", "3/2"));
+        assert!(!numeric_eq("2", "2.5"));
+        assert!(!numeric_eq("3/2", "1"));
+        assert!(!numeric_eq("9007199254740993", "9007199254740992.0"));
+        assert!(!numeric_eq("9223372036854775807", "9223372036854775807.0"));
+        assert!(!numeric_eq("\"2\"", "2"));
+        assert!(!numeric_eq("a", "a"));
+        assert!(!Float(super::Float::NAN).numeric_eq(&Float(super::Float::NAN)));
+    }
+
+    #[test]
+    fn atom_accessors() {
+        let atoms = [
+            Int(3),
+            Float(1.5),
+            Rational { num: 1, den: 4 },
+            Symbol("car".into()),
+            Keyword("key".to_owned()),
+            Str("hi".to_owned()),
+            Char('c'),
+            Bool(false),
+            Nil,
+        ];
+        let found = |f: fn(&super::Atom) -> bool| {
+            atoms
+                .iter()
+                .filter(|atom| f(atom))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(Int(3).as_int(), Some(3));
+        assert_eq!(found(|a| a.as_int().is_some()), [Int(3)]);
+        assert_eq!(Float(1.5).as_float(), Some(1.5));
+        assert_eq!(found(|a| a.as_float().is_some()), [Float(1.5)]);
+        assert_eq!(Symbol("car".into()).as_symbol(), Some("car"));
+        assert_eq!(found(|a| a.as_symbol().is_some()), [Symbol("car".into())]);
+        assert_eq!(Keyword("key".to_owned()).as_keyword(), Some("key"));
         assert_eq!(
-            parse_sexpr("(+ 1 2").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
+            found(|a| a.as_keyword().is_some()),
+            [Keyword("key".to_owned())]
         );
+        assert_eq!(Str("hi".to_owned()).as_str(), Some("hi"));
+        assert_eq!(found(|a| a.as_str().is_some()), [Str("hi".to_owned())]);
+        assert_eq!(Char('c').as_char(), Some('c'));
+        assert_eq!(found(|a| a.as_char().is_some()), [Char('c')]);
+        assert_eq!(Bool(false).as_bool(), Some(false));
+        assert_eq!(found(|a| a.as_bool().is_some()), [Bool(false)]);
+
+        assert_eq!(Int(3).as_float_lossy(), Some(3.0));
+        assert_eq!(Float(1.5).as_float_lossy(), Some(1.5));
+        assert_eq!(Rational { num: 1, den: 4 }.as_float_lossy(), Some(0.25));
+        assert_eq!(found(|a| a.as_float_lossy().is_some()).len(), 3);
     }
 
     #[test]
-    fn unmatched_front_paren_err() {
+    fn atom_kinds() {
+        let node = parse_sexpr("(a 1 1.5 1/2 :k \"s\" #\\c #t)").unwrap();
+        let kinds: Vec<_> = node
+            .children()
+            .map(|n| n.as_atom().unwrap().kind())
+            .collect();
+
         assert_eq!(
-            parse_sexpr("+ 1 2)").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
-        )
+            kinds,
+            ["Symbol", "Int", "Float", "Rational", "Keyword", "Str", "Char", "Bool"]
+        );
+        assert_eq!(Nil.kind(), "Nil");
     }
 
     #[test]
-    fn unmatched_extra_paren_err() {
-        assert_eq!(
-            parse_sexpr("(+ 1 2))").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
-        )
+    fn node_as_atom() {
+        assert_eq!(Node::int(1).as_atom(), Some(&Int(1)));
+        assert_eq!(parse_sexpr("(1)").unwrap().as_atom(), None);
+        assert_eq!(parse_sexpr("(1 . 2)").unwrap().as_atom(), None);
+        assert_eq!(parse_sexpr("#(1)").unwrap().as_atom(), None);
     }
 
     #[test]
-    fn unmatched_paren_nested_err() {
+    fn compare_with_symbol_names() {
+        assert_eq!(Symbol("x".into()), "x");
+        assert!(Symbol("define".into()) == *"define");
+        assert_ne!(Symbol("x".into()), "y");
+        assert_ne!(Str("x".to_owned()), "x");
+        assert_ne!(Keyword("x".to_owned()), "x");
+
+        let node = parse_sexpr("(define x \"x\")").unwrap();
+        let elements = node.as_list().unwrap();
+
+        assert_eq!(elements[0], "define");
+        assert_eq!(elements[1], "x");
+        assert_ne!(elements[2], "x");
+        assert_ne!(node, "define");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn int_float_ordering() {
+        use super::cmp_int_float;
+        use core::cmp::Ordering::*;
+
+        assert_eq!(cmp_int_float(1, 1.5), Some(Less));
+        assert_eq!(cmp_int_float(-1, -1.5), Some(Greater));
+        assert_eq!(cmp_int_float(2, 2.0), Some(Equal));
+        assert_eq!(cmp_int_float(i64::MAX, 9.3e18), Some(Less));
+        assert_eq!(cmp_int_float(i64::MIN, -9223372036854775808.0), Some(Equal));
+        assert_eq!(cmp_int_float(0, super::Float::INFINITY), Some(Less));
+        assert_eq!(cmp_int_float(0, super::Float::NEG_INFINITY), Some(Greater));
+        assert_eq!(cmp_int_float(0, super::Float::NAN), None);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_numeric_eq() {
+        // 2^70, which both sizes of float hold exactly.
+        let big = parse_sexpr("1180591620717411303424")
+            .unwrap()
+            .as_atom()
+            .cloned()
+            .unwrap();
+
+        assert!(big.numeric_eq(&Float(1180591620717411303424.0)));
+        assert!(Float(1180591620717411303424.0).numeric_eq(&big));
+        assert!(!big.numeric_eq(&Float(1.5e20)));
+        assert!(!big.numeric_eq(&Int(1)));
+    }
+
+    #[test]
+    fn nodes_in_hash_set() {
+        use std::collections::HashSet;
+
+        let forms = parse_program("(a 1) (a 1.5) (a . 1) \"a\" :a a 3/4 (a 1) a 0.0 -0.0").unwrap();
+        let set: HashSet<_> = forms.iter().cloned().collect();
+
+        assert_eq!(set.len(), 8);
+
+        for form in forms.iter() {
+            assert!(set.contains(form), "{}", form);
+        }
+
+        assert!(!set.contains(&parse_sexpr("(a 2)").unwrap()));
+        assert!(set.contains(&Node::float(-0.0)));
+    }
+
+    #[test]
+    fn incomplete_input() {
+        for code in [
+            "(",
+            "(define (f x)\n",
+            "'",
+            "(a '",
+            "\"unfinished",
+            "#| comment",
+        ]
+        .iter()
+        {
+            assert!(!is_complete(code), "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn complete_or_malformed_input() {
+        for code in [
+            "",
+            "a",
+            "(a b)",
+            "(a)\n(b)",
+            ")",
+            "(a))",
+            "(a ')",
+            "(a . )",
+            "\"s\" ; (comment",
+        ]
+        .iter()
+        {
+            assert!(is_complete(code), "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(parse_sexpr("").unwrap_err().position(), None);
         assert_eq!(
-            parse_sexpr("(+ (+ 3 4 2)").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
+            parse_sexpr("(a\n  b))").unwrap_err().position(),
+            Some(pos(7, 2, 5))
+        );
+        assert_eq!(
+            parse_sexpr("(x #\\bogus)").unwrap_err().position(),
+            Some(pos(3, 1, 4))
         );
     }
 
     #[test]
-    fn unmatched_extra_paren_nested_err() {
-        assert_eq!(
-            parse_sexpr("(+ (+ 3 4 2)))").unwrap_err(),
-            SexprSyntaxError::UnmatchedParen,
-        )
+    fn error_codes() {
+        let p = pos(0, 1, 1);
+        let s = String::new;
+        let errors = [
+            (SexprSyntaxError::Empty, "empty"),
+            (SexprSyntaxError::UnexpectedEof(p), "unexpected-eof"),
+            (SexprSyntaxError::UnexpectedCloseParen(p), "unmatched-paren"),
+            (
+                SexprSyntaxError::MismatchedBracket { open: p, close: p },
+                "mismatched-bracket",
+            ),
+            (SexprSyntaxError::TrailingTokens(p), "trailing-tokens"),
+            (
+                SexprSyntaxError::UnterminatedString(p),
+                "unterminated-string",
+            ),
+            (
+                SexprSyntaxError::UnterminatedSymbol(p),
+                "unterminated-symbol",
+            ),
+            (SexprSyntaxError::InvalidEscape('q', p), "invalid-escape"),
+            (
+                SexprSyntaxError::UnterminatedComment(p),
+                "unterminated-comment",
+            ),
+            (
+                SexprSyntaxError::DanglingReaderMacro(p),
+                "dangling-reader-macro",
+            ),
+            (
+                SexprSyntaxError::DisabledReaderMacro(p),
+                "disabled-reader-macro",
+            ),
+            (
+                SexprSyntaxError::InvalidDottedPair(p),
+                "invalid-dotted-pair",
+            ),
+            (SexprSyntaxError::InvalidNumber(s(), p), "invalid-number"),
+            (
+                SexprSyntaxError::IntegerOverflow(s(), p),
+                "integer-overflow",
+            ),
+            (SexprSyntaxError::InvalidChar(s(), p), "invalid-char"),
+            (SexprSyntaxError::InvalidSymbol(s(), p), "invalid-symbol"),
+            (
+                SexprSyntaxError::DepthLimitExceeded(p),
+                "depth-limit-exceeded",
+            ),
+        ];
+
+        for (error, code) in errors.iter() {
+            assert_eq!(error.code(), *code, "{:?}", error);
+        }
+
+        assert_eq!(parse_sexpr("(a))").unwrap_err().code(), "unmatched-paren");
+    }
+
+    fn span(span: std::ops::Range<usize>, children: Vec<Spans>) -> Spans {
+        Spans { span, children }
     }
 
     #[test]
-    fn basic_nested() {
+    fn spans_of_nested_lists() {
+        let code = "(+ 1 (f \"x\"))";
+        let (node, spans) = parse_sexpr_spanned(code).unwrap();
+
+        assert_eq!(node, parse_sexpr(code).unwrap());
         assert_eq!(
-            parse_sexpr("(+ (+ 1 2) 3)").unwrap(),
-            List(vec![
-                Atom(Symbol("+".to_owned())),
-                List(vec![
-                    Atom(Symbol("+".to_owned())),
-                    Atom(Int(1)),
-                    Atom(Int(2))
-                ]),
-                Atom(Int(3))
-            ],),
+            spans,
+            Spans {
+                span: 0..13,
+                children: vec![
+                    span(1..2, vec![]),
+                    span(3..4, vec![]),
+                    span(5..12, vec![span(6..7, vec![]), span(8..11, vec![])]),
+                ],
+            }
         );
+        assert_eq!(&code[spans.children[2].span.clone()], "(f \"x\")");
     }
 
     #[test]
-    fn multiple_nested() {
+    fn spans_line_up_with_children() {
+        for code in [
+            "(a b . c)",
+            "'(a ,b)",
+            "(() (x . (y z)) ((1)))",
+            "#(a #(b) (c))",
+            "atom",
+        ]
+        .iter()
+        {
+            let (node, spans) = parse_sexpr_spanned(code).unwrap();
+            let mut stack = vec![(&node, &spans)];
+
+            while let Some((node, spans)) = stack.pop() {
+                assert_eq!(node.children().count(), spans.children.len(), "{}", code);
+
+                stack.extend(node.children().zip(&spans.children));
+            }
+        }
+    }
+
+    #[test]
+    fn spans_of_dotted_pairs_and_reader_macros() {
         assert_eq!(
-            parse_sexpr("(+ (+ 1 2) (+ 3 4))").unwrap(),
-            List(vec![
-                Atom(Symbol("+".to_owned())),
-                List(vec![
-                    Atom(Symbol("+".to_owned())),
-                    Atom(Int(1)),
-                    Atom(Int(2))
-                ]),
-                List(vec![
-                    Atom(Symbol("+".to_owned())),
-                    Atom(Int(3)),
-                    Atom(Int(4))
-                ]),
-            ],),
+            parse_sexpr_spanned("(a . b)").unwrap().1,
+            span(0..7, vec![span(1..2, vec![]), span(5..6, vec![])]),
+        );
+        assert_eq!(
+            parse_sexpr_spanned("'(a)").unwrap().1,
+            span(
+                0..4,
+                vec![span(0..1, vec![]), span(1..4, vec![span(2..3, vec![])])]
+            ),
         );
     }
 
     #[test]
-    fn long_opname_multiple_nested() {
+    fn program_spans() {
+        let forms = parse_program_spanned("(a) ; comment\n  b\n").unwrap();
+
         assert_eq!(
-            parse_sexpr("(add (add 1 2) (add 3 4))").unwrap(),
-            List(vec![
-                Atom(Symbol("add".to_owned())),
-                List(vec![
-                    Atom(Symbol("add".to_owned())),
-                    Atom(Int(1)),
-                    Atom(Int(2)),
-                ]),
-                List(vec![
-                    Atom(Symbol("add".to_owned())),
-                    Atom(Int(3)),
-                    Atom(Int(4))
-                ]),
-            ],),
+            forms,
+            vec![
+                (
+                    Node::list([Node::symbol("a")]),
+                    span(0..3, vec![span(1..2, vec![])])
+                ),
+                (Node::symbol("b"), span(16..17, vec![])),
+            ]
+        );
+        assert_eq!(
+            parse_program_spanned("(a) (b").unwrap_err(),
+            SexprSyntaxError::UnexpectedEof(pos(4, 1, 5)),
         );
     }
 
     #[test]
-    fn deeply_nested() {
-        let sexpr = "(car (list 1 (+ 2 3) (* (+ 4 5) 6)))";
+    fn truncated_input_does_not_panic() {
+        let samples = [
+            "(display \"tab\\tnew\\nline \\\"q\\\"\")",
+            "(f ; comment\r\n  x #| block #| nested |# |# y)",
+            "(#\\a #\\λ #\\( #\\newline '(a `(b ,c ,@d)) . e)",
+            "(#x1F #b-101 3/4 -6/8 1e10 -2.5e-3 :k #t #(v |w\\|x|))",
+        ];
 
-        let expected_inner = List(vec![
-            Atom(Symbol("list".to_owned())),
-            Atom(Int(1)),
-            List(vec![
-                Atom(Symbol("+".to_owned())),
-                Atom(Int(2)),
-                Atom(Int(3)),
-            ]),
-            List(vec![
-                Atom(Symbol("*".to_owned())),
-                List(vec![
-                    Atom(Symbol("+".to_owned())),
-                    Atom(Int(4)),
-                    Atom(Int(5)),
-                ]),
-                Atom(Int(6)),
-            ]),
-        ]);
+        // Every prefix that ends on a character boundary, so that each kind
+        // of token gets cut off part of the way through.
+        for sample in samples.iter() {
+            for (end, _) in sample.char_indices() {
+                let prefix = &sample[..end];
 
-        assert_eq!(
-            parse_sexpr(sexpr).unwrap(),
-            List(vec![Atom(Symbol("car".to_owned())), expected_inner,])
-        );
+                if let Err(e) = parse_sexpr(prefix) {
+                    let _ = e.to_string();
+                }
+
+                let _ = parse_program_spanned(prefix);
+            }
+        }
     }
 }
@@ -0,0 +1,24 @@
+//! The types and functions most code using the crate needs, for importing
+//! all at once with `use lisp_rs::prelude::*`:
+//!
+//! - `Node`, `Atom` and `Symbol`, which make up parsed code, along with the
+//!   `Node` constructors like `Node::list` and `Node::symbol` that come with
+//!   them.
+//! - `parse_sexpr` and `parse_program`, their `parse_sexpr_with` and
+//!   `parse_program_with` counterparts along with the `ParseOptions` those
+//!   take, and the `SexprSyntaxError` they all give.
+//! - With the `std` feature, `eval` and the `Env`, `Value` and `EvalError`
+//!   it works with.
+//!
+//! Everything else, like spans, comments, tokens and macros, is left to be
+//! imported from the crate root by the code that needs it.
+
+#[cfg(feature = "std")]
+pub use crate::eval::{eval, Env, EvalError, Value};
+pub use crate::{
+    parser::{
+        parse_program, parse_program_with, parse_sexpr, parse_sexpr_with, Atom, Node, ParseOptions,
+        SexprSyntaxError,
+    },
+    symbol::Symbol,
+};
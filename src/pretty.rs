@@ -0,0 +1,472 @@
+use alloc::{
+    string::{String, ToString},
+    vec,
+};
+
+use crate::parser::{Atom, Node};
+
+impl Node {
+    /// Renders the node across multiple lines, keeping it within `width`
+    /// columns where possible. This is `PrettyPrinter`'s default formatting;
+    /// see there for the details and for other options.
+    pub fn pretty(&self, width: usize) -> String {
+        PrettyPrinter::new().max_width(width).format(self)
+    }
+
+    /// Renders the node as an indented tree with one line per node, each
+    /// labelled with its kind, as in `Int 1` or `Symbol +`. A chain of pairs
+    /// is shown as a single `DottedList`, with the head of each pair and then
+    /// the final tail beneath it, so that a long one isn't indented ever
+    /// further. This
+    /// is meant for looking at parser output while debugging, and unlike
+    /// `Display` it isn't meant to be read back in.
+    pub fn debug_tree(&self) -> String {
+        let mut out = String::new();
+        write_tree(self, 0, &mut out);
+
+        out
+    }
+}
+
+fn write_tree(node: &Node, depth: usize, out: &mut String) {
+    if depth > 0 {
+        out.push('\n');
+    }
+
+    out.push_str(&"  ".repeat(depth));
+
+    match node {
+        Node::List(v) | Node::Vector(v) => {
+            out.push_str(match node {
+                Node::Vector(_) => "Vector",
+                _ => "List",
+            });
+
+            for element in v {
+                write_tree(element, depth + 1, out);
+            }
+        }
+        Node::Pair(..) => {
+            out.push_str("DottedList");
+
+            for child in node.children() {
+                write_tree(child, depth + 1, out);
+            }
+        }
+        Node::Atom(atom) => {
+            out.push_str(atom.kind());
+
+            // There's only the one `nil`, so there's nothing to add.
+            if *atom == Atom::Nil {
+                return;
+            }
+
+            out.push(' ');
+            out.push_str(&atom.to_string());
+        }
+    }
+}
+
+/// Formats nodes across multiple lines. Any list that fits on the rest of
+/// its line is printed as `Display` would print it. Otherwise its head stays
+/// on the opening line and every other element goes on a line of its own.
+///
+/// Only the list itself is measured, not the closing parens of the lists
+/// around it, so a line can end up a few columns past the maximum width.
+#[derive(Debug, Clone)]
+pub struct PrettyPrinter {
+    indent: usize,
+    max_width: usize,
+    align: bool,
+}
+
+impl PrettyPrinter {
+    /// Creates a printer that indents by 2 columns and wraps at 80.
+    pub fn new() -> Self {
+        Self {
+            indent: 2,
+            max_width: 80,
+            align: false,
+        }
+    }
+
+    /// Sets how many columns further than its opening paren the elements of
+    /// a broken list are indented.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets the width lists are wrapped to fit within.
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Sets whether the elements of a broken list line up under its head
+    /// instead, regardless of the indent.
+    pub fn align(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn format(&self, node: &Node) -> String {
+        let mut out = String::new();
+        self.write(node, 0, &mut out);
+
+        out
+    }
+
+    /// Writes `node` into `out`, assuming it starts at `column`.
+    fn write(&self, node: &Node, column: usize, out: &mut String) {
+        let flat = node.to_string();
+
+        if column + flat.chars().count() <= self.max_width {
+            out.push_str(&flat);
+            return;
+        }
+
+        let mut elements = vec![];
+        let mut tail = None;
+        let open = match node {
+            Node::Vector(_) => "#(",
+            _ => "(",
+        };
+
+        match node {
+            Node::List(v) | Node::Vector(v) => elements.extend(v),
+            Node::Pair(car, cdr) => {
+                elements.push(&**car);
+
+                let mut rest = &**cdr;
+
+                while let Node::Pair(car, cdr) = rest {
+                    elements.push(car);
+                    rest = cdr;
+                }
+
+                tail = Some(rest);
+            }
+            Node::Atom(_) => {}
+        }
+
+        let (head, rest) = match elements.split_first() {
+            Some(split) => split,
+            None => {
+                out.push_str(&flat);
+                return;
+            }
+        };
+
+        let indent = if self.align {
+            column + open.len()
+        } else {
+            column + self.indent
+        };
+
+        out.push_str(open);
+        self.write(head, column + open.len(), out);
+
+        for element in rest {
+            newline(indent, out);
+            self.write(element, indent, out);
+        }
+
+        if let Some(tail) = tail {
+            newline(indent, out);
+            out.push_str(". ");
+            self.write(tail, indent + 2, out);
+        }
+
+        out.push(')');
+    }
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Spacing choices for printing a node on a single line, so that generated
+/// code can follow the conventions of a particular dialect. The default style
+/// prints exactly what `Display` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DisplayStyle {
+    pad_parens: bool,
+    space_empty: bool,
+}
+
+impl DisplayStyle {
+    /// Creates the style `Display` uses, with no spaces inside parens.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether lists, vectors and pairs with anything in them have a
+    /// space inside each paren, as in `( a b )`.
+    pub fn pad_parens(mut self, pad: bool) -> Self {
+        self.pad_parens = pad;
+        self
+    }
+
+    /// Sets whether an empty list or vector is printed as `( )` rather than
+    /// `()`.
+    pub fn space_empty(mut self, space: bool) -> Self {
+        self.space_empty = space;
+        self
+    }
+
+    pub fn format(&self, node: &Node) -> String {
+        let mut out = String::new();
+        self.write(node, &mut out);
+
+        out
+    }
+
+    fn write(&self, node: &Node, out: &mut String) {
+        let mut elements = vec![];
+        let mut tail = None;
+
+        match node {
+            Node::List(v) | Node::Vector(v) => elements.extend(v),
+            Node::Pair(car, cdr) => {
+                elements.push(&**car);
+
+                let mut rest = &**cdr;
+
+                while let Node::Pair(car, cdr) = rest {
+                    elements.push(car);
+                    rest = cdr;
+                }
+
+                tail = Some(rest);
+            }
+            Node::Atom(atom) => return out.push_str(&atom.to_string()),
+        }
+
+        out.push_str(match node {
+            Node::Vector(_) => "#(",
+            _ => "(",
+        });
+
+        let empty = elements.is_empty();
+
+        if (empty && self.space_empty) || (!empty && self.pad_parens) {
+            out.push(' ');
+        }
+
+        for (i, element) in elements.into_iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+
+            self.write(element, out);
+        }
+
+        if let Some(tail) = tail {
+            out.push_str(" . ");
+            self.write(tail, out);
+        }
+
+        if !empty && self.pad_parens {
+            out.push(' ');
+        }
+
+        out.push(')');
+    }
+}
+
+fn newline(indent: usize, out: &mut String) {
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayStyle, PrettyPrinter};
+    use crate::parser::parse_sexpr;
+
+    const DEEPLY_NESTED: &str = "(car (list 1 (+ 2 3) (* (+ 4 5) 6)))";
+
+    #[test]
+    fn short_list_stays_on_one_line() {
+        let node = parse_sexpr(DEEPLY_NESTED).unwrap();
+
+        assert_eq!(node.pretty(80), DEEPLY_NESTED);
+        assert_eq!(node.pretty(DEEPLY_NESTED.len()), DEEPLY_NESTED);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let node = parse_sexpr(DEEPLY_NESTED).unwrap();
+
+        assert_eq!(node.pretty(34), "(car\n  (list 1 (+ 2 3) (* (+ 4 5) 6)))");
+        assert_eq!(
+            node.pretty(17),
+            "(car\n  (list\n    1\n    (+ 2 3)\n    (* (+ 4 5) 6)))"
+        );
+        assert_eq!(
+            node.pretty(13),
+            "(car\n  (list\n    1\n    (+ 2 3)\n    (*\n      (+ 4 5)\n      6)))"
+        );
+    }
+
+    #[test]
+    fn reparses_to_the_same_node() {
+        let node = parse_sexpr(DEEPLY_NESTED).unwrap();
+
+        for width in 0..40 {
+            assert_eq!(parse_sexpr(&node.pretty(width)).unwrap(), node);
+        }
+    }
+
+    #[test]
+    fn debug_tree() {
+        assert_eq!(
+            parse_sexpr("(+ 1 2)").unwrap().debug_tree(),
+            "List\n  Symbol +\n  Int 1\n  Int 2"
+        );
+        assert_eq!(
+            parse_sexpr("(f \"s\" (a . 1.5) ())").unwrap().debug_tree(),
+            concat!(
+                "List\n",
+                "  Symbol f\n",
+                "  Str \"s\"\n",
+                "  DottedList\n",
+                "    Symbol a\n",
+                "    Float 1.5\n",
+                "  List",
+            )
+        );
+        assert_eq!(
+            parse_sexpr("(a (b . c) . d)").unwrap().debug_tree(),
+            concat!(
+                "DottedList\n",
+                "  Symbol a\n",
+                "  DottedList\n",
+                "    Symbol b\n",
+                "    Symbol c\n",
+                "  Symbol d",
+            )
+        );
+    }
+
+    #[test]
+    fn debug_tree_of_a_long_dotted_list() {
+        let code = format!("({}. y)", "x ".repeat(100_000));
+        let expected = format!("DottedList{}\n  Symbol y", "\n  Symbol x".repeat(100_000));
+
+        std::thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                assert_eq!(parse_sexpr(&code).unwrap().debug_tree(), expected);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn vectors() {
+        let node = parse_sexpr("(f #(alpha beta gamma))").unwrap();
+
+        assert_eq!(node.pretty(14), "(f\n  #(alpha\n    beta\n    gamma))");
+        assert_eq!(
+            PrettyPrinter::new().max_width(14).align(true).format(&node),
+            "(f\n #(alpha\n   beta\n   gamma))"
+        );
+        assert_eq!(parse_sexpr("#(1)").unwrap().debug_tree(), "Vector\n  Int 1");
+    }
+
+    #[test]
+    fn improper_list() {
+        let node = parse_sexpr("(alpha beta . (gamma delta))").unwrap();
+
+        assert_eq!(node.pretty(12), "(alpha\n  beta\n  . (gamma\n      delta))");
+        assert_eq!(parse_sexpr(&node.pretty(12)).unwrap(), node);
+    }
+
+    #[test]
+    fn atoms_are_never_broken() {
+        let node = parse_sexpr("\"a long string literal\"").unwrap();
+
+        assert_eq!(node.pretty(4), "\"a long string literal\"");
+    }
+
+    #[test]
+    fn indent_widths() {
+        let node = parse_sexpr("(define (square x) (* x x))").unwrap();
+        let printer = PrettyPrinter::new().max_width(20);
+
+        assert_eq!(
+            printer.clone().indent(2).format(&node),
+            "(define\n  (square x)\n  (* x x))"
+        );
+        assert_eq!(
+            printer.clone().indent(4).format(&node),
+            "(define\n    (square x)\n    (* x x))"
+        );
+        assert_eq!(
+            printer.indent(0).format(&node),
+            "(define\n(square x)\n(* x x))"
+        );
+    }
+
+    #[test]
+    fn align_under_operator() {
+        let node = parse_sexpr("(let ((x 1) (y 2)) (+ x y))").unwrap();
+
+        assert_eq!(
+            PrettyPrinter::new()
+                .indent(4)
+                .max_width(16)
+                .align(true)
+                .format(&node),
+            "(let\n ((x 1) (y 2))\n (+ x y))"
+        );
+    }
+
+    #[test]
+    fn default_matches_pretty() {
+        let node = parse_sexpr(DEEPLY_NESTED).unwrap();
+
+        assert_eq!(
+            PrettyPrinter::new().max_width(13).format(&node),
+            node.pretty(13)
+        );
+        assert_eq!(PrettyPrinter::default().format(&node), DEEPLY_NESTED);
+    }
+
+    #[test]
+    fn display_styles() {
+        let empty = parse_sexpr("()").unwrap();
+        let nested = parse_sexpr("(a () (b #(c) . d) #())").unwrap();
+        let styles = [
+            (DisplayStyle::new(), "()", "(a () (b #(c) . d) #())"),
+            (
+                DisplayStyle::new().space_empty(true),
+                "( )",
+                "(a ( ) (b #(c) . d) #( ))",
+            ),
+            (
+                DisplayStyle::new().pad_parens(true),
+                "()",
+                "( a () ( b #( c ) . d ) #() )",
+            ),
+            (
+                DisplayStyle::new().pad_parens(true).space_empty(true),
+                "( )",
+                "( a ( ) ( b #( c ) . d ) #( ) )",
+            ),
+        ];
+
+        for (style, empty_text, nested_text) in styles {
+            assert_eq!(style.format(&empty), empty_text);
+            assert_eq!(style.format(&nested), nested_text);
+            assert_eq!(parse_sexpr(nested_text).unwrap(), nested);
+        }
+
+        assert_eq!(DisplayStyle::new().format(&nested), nested.to_string());
+    }
+}
@@ -0,0 +1,209 @@
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    mem,
+};
+
+use crate::parser::{Atom, Node};
+
+/// A `Node` whose lists and pairs share their contents through reference
+/// counting, so that cloning one, or any part of one, costs the same however
+/// much is inside it. This suits code such as a macro expander, which copies
+/// subtrees around a lot without changing them. Atoms are still cloned
+/// outright, which for strings means copying them.
+///
+/// The owned `Node` stays what the parser produces; convert between the two
+/// with `Node::to_shared` and `SharedNode::to_node`. As with `Node`, chains
+/// of pairs are followed in loops, so a long dotted list doesn't overflow the
+/// stack.
+#[derive(Debug, Clone)]
+pub enum SharedNode {
+    List(Rc<[SharedNode]>),
+    Pair(Rc<SharedNode>, Rc<SharedNode>),
+    Vector(Rc<[SharedNode]>),
+    Atom(Atom),
+}
+
+impl Node {
+    pub fn to_shared(&self) -> SharedNode {
+        match self {
+            Node::List(v) => SharedNode::List(v.iter().map(Node::to_shared).collect()),
+            Node::Pair(..) => {
+                let mut cars = vec![];
+                let mut tail = self;
+
+                while let Node::Pair(car, cdr) = tail {
+                    cars.push(car);
+                    tail = cdr;
+                }
+
+                cars.into_iter().rev().fold(tail.to_shared(), |cdr, car| {
+                    SharedNode::Pair(Rc::new(car.to_shared()), Rc::new(cdr))
+                })
+            }
+            Node::Vector(v) => SharedNode::Vector(v.iter().map(Node::to_shared).collect()),
+            Node::Atom(atom) => SharedNode::Atom(atom.clone()),
+        }
+    }
+}
+
+impl SharedNode {
+    /// Copies this tree back out into an owned `Node`.
+    pub fn to_node(&self) -> Node {
+        match self {
+            SharedNode::List(v) => Node::List(v.iter().map(SharedNode::to_node).collect()),
+            SharedNode::Pair(..) => {
+                let mut cars = vec![];
+                let mut tail = self;
+
+                while let SharedNode::Pair(car, cdr) = tail {
+                    cars.push(car);
+                    tail = cdr;
+                }
+
+                cars.into_iter().rev().fold(tail.to_node(), |cdr, car| {
+                    Node::Pair(Box::new(car.to_node()), Box::new(cdr))
+                })
+            }
+            SharedNode::Vector(v) => Node::Vector(v.iter().map(SharedNode::to_node).collect()),
+            SharedNode::Atom(atom) => Node::Atom(atom.clone()),
+        }
+    }
+
+    /// Returns the elements of a list, or `None` for anything else.
+    pub fn as_list(&self) -> Option<&[SharedNode]> {
+        match self {
+            SharedNode::List(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for SharedNode {
+    fn drop(&mut self) {
+        // Detach each tail that nothing else shares before the pair holding
+        // it is dropped, so that every pair is dropped with an empty list as
+        // its tail. A shared tail is left to whoever else holds it.
+        let empty = || Rc::new(SharedNode::List(Rc::from(Vec::new())));
+        let mut tail = match self {
+            Self::Pair(_, cdr) => mem::replace(cdr, empty()),
+            _ => return,
+        };
+
+        while let Ok(mut node) = Rc::try_unwrap(tail) {
+            tail = match &mut node {
+                Self::Pair(_, cdr) => mem::replace(cdr, empty()),
+                _ => return,
+            };
+        }
+    }
+}
+
+impl PartialEq for SharedNode {
+    fn eq(&self, other: &SharedNode) -> bool {
+        let (mut a, mut b) = (self, other);
+
+        loop {
+            match (a, b) {
+                (Self::Pair(car_a, cdr_a), Self::Pair(car_b, cdr_b)) => {
+                    if car_a != car_b {
+                        return false;
+                    }
+
+                    a = cdr_a;
+                    b = cdr_b;
+                }
+                (Self::List(a), Self::List(b)) | (Self::Vector(a), Self::Vector(b)) => {
+                    return a == b
+                }
+                (Self::Atom(a), Self::Atom(b)) => return a == b,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Eq for SharedNode {}
+
+impl Hash for SharedNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut node = self;
+
+        loop {
+            mem::discriminant(node).hash(state);
+
+            match node {
+                Self::Pair(car, cdr) => {
+                    car.hash(state);
+                    node = cdr;
+                }
+                Self::List(v) | Self::Vector(v) => return v.hash(state),
+                Self::Atom(a) => return a.hash(state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::SharedNode;
+    use crate::parser::parse_sexpr;
+
+    #[test]
+    fn roundtrip() {
+        for code in ["(a (b . c) #(1 \"two\") ())", "atom", "(x y . z)"].iter() {
+            let node = parse_sexpr(code).unwrap();
+
+            assert_eq!(node.to_shared().to_node(), node, "{}", code);
+        }
+    }
+
+    #[test]
+    fn long_dotted_list_on_a_small_stack() {
+        use std::{collections::hash_map::DefaultHasher, hash::Hash, thread};
+
+        let code = format!("({}. y)", "x ".repeat(100_000));
+
+        thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                let node = parse_sexpr(&code).unwrap();
+                let shared = node.to_shared();
+                let copy = node.to_shared();
+                let clone = shared.clone();
+
+                assert_eq!(shared, copy);
+                assert_eq!(shared.to_node(), node);
+
+                let mut hasher = DefaultHasher::new();
+                shared.hash(&mut hasher);
+                drop(shared);
+                drop(copy);
+                drop(clone);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn clones_share_their_contents() {
+        let shared = parse_sexpr("(f (g x) (h y))").unwrap().to_shared();
+        let copy = shared.clone();
+
+        match (&shared, &copy) {
+            (SharedNode::List(a), SharedNode::List(b)) => assert!(Rc::ptr_eq(a, b)),
+            _ => panic!("expected lists"),
+        }
+
+        let inner = shared.as_list().unwrap()[1].clone();
+
+        assert_eq!(inner, parse_sexpr("(g x)").unwrap().to_shared());
+        assert!(std::ptr::eq(
+            inner.as_list().unwrap(),
+            shared.as_list().unwrap()[1].as_list().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, vec::Vec};
+use core::{convert::TryFrom, fmt};
+#[cfg(feature = "std")]
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An interned symbol name. Every symbol with the same name shares a single
+/// copy of it in a table that lives for the rest of the program, so symbols
+/// are cheap to copy and compare, at the cost of never freeing a name once
+/// it's been seen.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[cfg(feature = "std")]
+type Ids = HashMap<&'static str, u32>;
+// There's no `HashMap` without `std`, since nothing in `alloc` can seed its
+// hasher randomly.
+#[cfg(not(feature = "std"))]
+type Ids = BTreeMap<&'static str, u32>;
+
+#[derive(Default)]
+struct Interner {
+    ids: Ids,
+    names: Vec<&'static str>,
+}
+
+#[cfg(feature = "std")]
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+    f(&mut INTERNER.get_or_init(Mutex::default).lock().unwrap())
+}
+
+/// Without `std` there's no `Mutex` either, so the table is guarded by a
+/// spin lock instead. A panic while it's held leaves it locked for good,
+/// which matters little on targets where a panic aborts anyway.
+#[cfg(not(feature = "std"))]
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    use core::{
+        cell::UnsafeCell,
+        hint,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    struct SpinLock {
+        locked: AtomicBool,
+        interner: UnsafeCell<Interner>,
+    }
+
+    // Safety: the interner is only ever reached while holding `locked`.
+    unsafe impl Sync for SpinLock {}
+
+    static INTERNER: SpinLock = SpinLock {
+        locked: AtomicBool::new(false),
+        interner: UnsafeCell::new(Interner {
+            ids: BTreeMap::new(),
+            names: Vec::new(),
+        }),
+    };
+
+    while INTERNER
+        .locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        hint::spin_loop();
+    }
+
+    // Safety: holding `locked` means nothing else has a reference to it.
+    let result = f(unsafe { &mut *INTERNER.interner.get() });
+
+    INTERNER.locked.store(false, Ordering::Release);
+    result
+}
+
+impl Symbol {
+    /// Finds the symbol for `name`, adding it to the table if it's new.
+    pub fn new(name: &str) -> Self {
+        with_interner(|interner| {
+            if let Some(&id) = interner.ids.get(name) {
+                return Self(id);
+            }
+
+            let id = u32::try_from(interner.names.len()).expect("too many distinct symbols");
+            let name: &'static str = Box::leak(name.into());
+
+            interner.ids.insert(name, id);
+            interner.names.push(name);
+
+            Self(id)
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        with_interner(|interner| interner.names[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Shows the name rather than the index, which means nothing on its own.
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(&String::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn same_name_same_symbol() {
+        assert_eq!(Symbol::new("lambda"), Symbol::new("lambda"));
+        assert_ne!(Symbol::new("lambda"), Symbol::new("define"));
+        assert_eq!(Symbol::new("lambda"), Symbol::from("lambda"));
+    }
+
+    #[test]
+    fn names_survive_the_round_trip() {
+        for name in ["x", "list->vector", "", "λ", "with space"].iter() {
+            assert_eq!(Symbol::new(name).as_str(), *name);
+            assert_eq!(Symbol::new(name).to_string(), *name);
+        }
+    }
+
+    #[test]
+    fn compares_with_str() {
+        assert_eq!(Symbol::new("car"), "car");
+        assert!(Symbol::new("car") != "cdr");
+        assert_eq!(format!("{:?}", Symbol::new("car")), "\"car\"");
+    }
+}
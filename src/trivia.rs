@@ -0,0 +1,154 @@
+use alloc::vec::Vec;
+use core::{iter::Peekable, ops::Range};
+
+use crate::parser::{parse_program_spanned, tokenize, Node, SexprSyntaxError, Spans, TokenKind};
+
+/// A comment found by `parse_program_with_comments`, exactly as written,
+/// including the `;` or `#|` and `|#` around it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Comment<'a> {
+    pub text: &'a str,
+    pub span: Range<usize>,
+}
+
+/// The comments around a node and everything inside it. Like `Spans`, the
+/// children line up with `Node::children`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Trivia<'a> {
+    /// The comments between whatever came before the node and the node
+    /// itself.
+    pub leading: Vec<Comment<'a>>,
+    pub children: Vec<Trivia<'a>>,
+    /// The comments inside a list after its last element, before it's
+    /// closed.
+    pub trailing: Vec<Comment<'a>>,
+}
+
+/// Like `parse_program_spanned`, but keeping the comments as well, each one
+/// attached to the node it comes before. The trivia for the program as a
+/// whole has a child for each top-level form, and the comments after the
+/// last one as its trailing comments.
+pub fn parse_program_with_comments(
+    code: &str,
+) -> Result<(Vec<(Node, Spans)>, Trivia<'_>), SexprSyntaxError> {
+    let forms = parse_program_spanned(code)?;
+
+    // The program parsed, so every token in it lexes.
+    let mut comments = tokenize(code)
+        .keep_comments()
+        .filter_map(|token| match token {
+            Ok(token) => match token.kind {
+                TokenKind::Comment(text) => Some(Comment {
+                    text,
+                    span: token.span,
+                }),
+                _ => None,
+            },
+            Err(_) => None,
+        })
+        .peekable();
+
+    let children = forms
+        .iter()
+        .map(|(_, spans)| attach(spans, &mut comments))
+        .collect();
+
+    Ok((
+        forms,
+        Trivia {
+            leading: Vec::new(),
+            children,
+            trailing: comments.collect(),
+        },
+    ))
+}
+
+/// Takes the comments that belong to the node `spans` describes from the
+/// front of `comments`, which mustn't hold any from before the node's
+/// preceding sibling.
+fn attach<'a>(
+    spans: &Spans,
+    comments: &mut Peekable<impl Iterator<Item = Comment<'a>>>,
+) -> Trivia<'a> {
+    let leading = take_while_before(comments, spans.span.start);
+    let children = spans
+        .children
+        .iter()
+        .map(|child| attach(child, comments))
+        .collect();
+    let trailing = take_while_before(comments, spans.span.end);
+
+    Trivia {
+        leading,
+        children,
+        trailing,
+    }
+}
+
+fn take_while_before<'a>(
+    comments: &mut Peekable<impl Iterator<Item = Comment<'a>>>,
+    offset: usize,
+) -> Vec<Comment<'a>> {
+    let mut taken = Vec::new();
+
+    while let Some(comment) = comments.next_if(|comment| comment.span.start < offset) {
+        taken.push(comment);
+    }
+
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_program_with_comments, Comment};
+    use crate::parser::parse_sexpr;
+
+    fn text<'a>(comments: &[Comment<'a>]) -> Vec<&'a str> {
+        comments.iter().map(|comment| comment.text).collect()
+    }
+
+    #[test]
+    fn comment_before_form_is_kept() {
+        let code = "; Adds two numbers.\n(+ 1 2)";
+        let (forms, trivia) = parse_program_with_comments(code).unwrap();
+        let leading = &trivia.children[0].leading;
+
+        assert_eq!(
+            leading,
+            &[Comment {
+                text: "; Adds two numbers.",
+                span: 0..19,
+            }]
+        );
+
+        let reprinted = format!("{}\n{}", leading[0].text, forms[0].0);
+
+        assert_eq!(reprinted, code);
+    }
+
+    #[test]
+    fn comments_attach_to_the_next_node() {
+        let code = "(define x ; the name\n  #| the value |# 1\n  ; nothing after\n)\n; the end\n";
+        let (forms, trivia) = parse_program_with_comments(code).unwrap();
+        let define = &trivia.children[0];
+
+        assert_eq!(forms[0].0, parse_sexpr("(define x 1)").unwrap());
+        assert!(define.leading.is_empty());
+        assert!(define.children[1].leading.is_empty());
+        assert_eq!(
+            text(&define.children[2].leading),
+            ["; the name", "#| the value |#"]
+        );
+        assert_eq!(text(&define.trailing), ["; nothing after"]);
+        assert_eq!(text(&trivia.trailing), ["; the end"]);
+    }
+
+    #[test]
+    fn no_comments() {
+        let (_, trivia) = parse_program_with_comments("(a (b)) c").unwrap();
+
+        assert_eq!(trivia.children.len(), 2);
+        assert!(trivia.children[0].children[1].leading.is_empty());
+        assert!(trivia.trailing.is_empty());
+    }
+}
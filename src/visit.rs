@@ -0,0 +1,217 @@
+use alloc::{boxed::Box, vec};
+
+use crate::parser::{Atom, Node};
+
+/// Callbacks for `Node::walk`. Both do nothing by default, so a visitor only
+/// needs to implement the ones it cares about.
+pub trait Visitor {
+    /// Called on every list, pair and vector, before any of its children.
+    fn visit_list(&mut self, _list: &Node) {}
+
+    fn visit_atom(&mut self, _atom: &Atom) {}
+}
+
+impl Node {
+    /// Visits this node and everything inside it depth-first, in the order
+    /// they appear in the source. Like the parser, this keeps its own stack
+    /// rather than recursing, so it copes with any tree the parser can build.
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        visit(self, visitor);
+
+        let mut stack = vec![self.children()];
+
+        while let Some(children) = stack.last_mut() {
+            match children.next() {
+                Some(child) => {
+                    visit(child, visitor);
+                    stack.push(child.children());
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Builds a copy of this tree with every atom in it replaced by the
+    /// result of `f`, keeping the lists and pairs around them as they were.
+    pub fn map_atoms(&self, f: impl Fn(&Atom) -> Atom) -> Node {
+        map_atoms(self, &f)
+    }
+
+    /// Counts the atoms and lists in this tree, including itself. A chain of
+    /// pairs counts as a single list, as it does when walking the tree.
+    pub fn node_count(&self) -> usize {
+        1 + self.children().map(Node::node_count).sum::<usize>()
+    }
+
+    /// How deeply lists nest in this tree. An atom has a depth of 0 and a
+    /// list one more than its deepest element, so `()` has a depth of 1.
+    pub fn depth(&self) -> usize {
+        match self {
+            Node::Atom(_) => 0,
+            _ => 1 + self.children().map(Node::depth).max().unwrap_or(0),
+        }
+    }
+}
+
+fn map_atoms(node: &Node, f: &impl Fn(&Atom) -> Atom) -> Node {
+    match node {
+        Node::List(v) => Node::List(v.iter().map(|n| map_atoms(n, f)).collect()),
+        Node::Vector(v) => Node::Vector(v.iter().map(|n| map_atoms(n, f)).collect()),
+        Node::Pair(..) => {
+            let mut cars = vec![];
+            let mut tail = node;
+
+            while let Node::Pair(car, cdr) = tail {
+                cars.push(map_atoms(car, f));
+                tail = cdr;
+            }
+
+            cars.into_iter().rev().fold(map_atoms(tail, f), |cdr, car| {
+                Node::Pair(Box::new(car), Box::new(cdr))
+            })
+        }
+        Node::Atom(atom) => Node::Atom(f(atom)),
+    }
+}
+
+fn visit(node: &Node, visitor: &mut impl Visitor) {
+    match node {
+        Node::Atom(atom) => visitor.visit_atom(atom),
+        _ => visitor.visit_list(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Visitor;
+    use crate::parser::{parse_sexpr, Atom, Node};
+
+    #[derive(Default)]
+    struct SymbolCounter {
+        symbols: usize,
+    }
+
+    impl Visitor for SymbolCounter {
+        fn visit_atom(&mut self, atom: &Atom) {
+            if let Atom::Symbol(_) = atom {
+                self.symbols += 1;
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        visited: Vec<String>,
+    }
+
+    impl Visitor for Recorder {
+        fn visit_list(&mut self, list: &Node) {
+            self.visited
+                .push(format!("list of {}", list.children().count()));
+        }
+
+        fn visit_atom(&mut self, atom: &Atom) {
+            self.visited.push(atom.to_string());
+        }
+    }
+
+    #[test]
+    fn count_symbols_in_deeply_nested() {
+        let node = parse_sexpr("(car (list 1 (+ 2 3) (* (+ 4 5) 6)))").unwrap();
+        let mut counter = SymbolCounter::default();
+
+        node.walk(&mut counter);
+
+        assert_eq!(counter.symbols, 5);
+    }
+
+    #[test]
+    fn visits_depth_first_in_source_order() {
+        let node = parse_sexpr("(a (b . c) d)").unwrap();
+        let mut recorder = Recorder::default();
+
+        node.walk(&mut recorder);
+
+        assert_eq!(
+            recorder.visited,
+            vec!["list of 3", "a", "list of 2", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn walk_atom() {
+        let mut counter = SymbolCounter::default();
+
+        Node::symbol("x").walk(&mut counter);
+
+        assert_eq!(counter.symbols, 1);
+    }
+
+    #[test]
+    fn walk_deep_tree() {
+        let depth = 100_000;
+        let node = (0..depth).fold(Node::symbol("x"), |node, _| Node::list([node]));
+        let mut counter = SymbolCounter::default();
+
+        node.walk(&mut counter);
+
+        assert_eq!(counter.symbols, 1);
+
+        // Dropping the tree recurses, so take it apart by hand instead.
+        let mut node = node;
+
+        while let Node::List(elements) = &mut node {
+            node = elements.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn map_ints_in_multiple_nested() {
+        let node = parse_sexpr("(+ (+ 1 2) (+ 3 4))").unwrap();
+        let doubled = node.map_atoms(|atom| match atom {
+            Atom::Int(i) => Atom::Int(i * 2),
+            other => other.clone(),
+        });
+
+        assert_eq!(doubled, parse_sexpr("(+ (+ 2 4) (+ 6 8))").unwrap());
+    }
+
+    #[test]
+    fn map_renames_symbols_in_pairs() {
+        let node = parse_sexpr("(x (y . x) \"x\")").unwrap();
+        let renamed = node.map_atoms(|atom| match atom {
+            Atom::Symbol(s) if s == "x" => Atom::Symbol("z".into()),
+            other => other.clone(),
+        });
+
+        assert_eq!(renamed, parse_sexpr("(z (y . z) \"x\")").unwrap());
+    }
+
+    #[test]
+    fn node_count_and_depth_of_deeply_nested() {
+        let node = parse_sexpr("(car (list 1 (+ 2 3) (* (+ 4 5) 6)))").unwrap();
+
+        assert_eq!(node.node_count(), 16);
+        assert_eq!(node.depth(), 4);
+    }
+
+    #[test]
+    fn node_count_and_depth_of_small_trees() {
+        let cases = [
+            ("x", 1, 0),
+            ("()", 1, 1),
+            ("(())", 2, 2),
+            ("(a b . c)", 4, 1),
+            ("((a . b) c)", 5, 2),
+        ];
+
+        for (sexpr, count, depth) in cases.iter() {
+            let node = parse_sexpr(sexpr).unwrap();
+
+            assert_eq!(node.node_count(), *count, "{}", sexpr);
+            assert_eq!(node.depth(), *depth, "{}", sexpr);
+        }
+    }
+}